@@ -1,15 +1,64 @@
 # lists
+status-branch = Branch
+status-components = Components
+status-format = Sources format
+status-mirrors = Enabled mirrors
+speed-record = {$speed}, {$age} ago
+speed-record-failed = failed, {$age} ago
+speed-record-stale = stale
+history-age = {$age} ago
+history-initial = (before history was recorded)
+status-rebuild = Rebuild it from the current APT sources
+status-reset = Reset to the default configuration
+doctor-regenerate = Overwrite it with the sources generated from the configuration
+doctor-import = Take the changes into the configuration
+doctor-skip = Leave it as is
+sync-disable = Disable it
 
 # messages
 disable-comp = Disabling component {$comp} ...
 set-mirror = Setting {$mirror} as the default mirror!
 remove-mirror = Removing {$mirror} from sources.list ...
-write-sources = Writing /etc/apt/sources.list ...
+write-sources = Writing APT sources ...
 run-refresh = Refreshing local mirror metadata ... 
 activating-count-mirrors = Activating {$count} mirrors ...
 select-open-or-close-mirrors = Select to enable or disable mirror(s) ...
 help-message = Press [Space]/[Enter] to toggle selection, [Esc] to apply changes, [Ctrl-c] to abort.
 fix-custom-howto = This is usually caused by syntax errors, please check and manually edit the file listed above.
+set-format = Setting APT sources format to {$format} ...
+remove-legacy-sources = Removing legacy APT sources file {$path} ...
+dry-run-nothing-written = Dry run, nothing has been written and no refresh has been run.
+dry-run-no-changes = Dry run, there is nothing to change.
+recovered-commit = Finished writing the configuration interrupted in the last run.
+rolled-back = Restored the previous configuration.
+undo-to = Reverting to configuration {$id} ...
+waiting-for-lock = Waiting for {$path} to be unlocked ...
+status-backup = Saved a copy of the corrupted status file to {$path}.
+status-corrupted-prompt = How do you want to recover the status file?
+unknown-source-uri = {$uri} is not a known mirror, leaving it out.
+verify-ok = No problems found.
+doctor-fix-sources = How do you want to fix it?
+doctor-fix-legacy = Remove it?
+doctor-fix-mirror = Disable mirror {$mirror}?
+doctor-fix-branch = Switch to branch {$branch}?
+doctor-fix-comp = Disable component {$comp}?
+doctor-fixed = Applying the fixes ...
+doctor-fix-url = Update it?
+doctor-fix-replaced = Switch to mirror {$new}?
+sync-updated-url = Updated URL of mirror {$mirror}: {$old} -> {$new}
+sync-up-to-date = All enabled mirrors are up to date.
+sync-migrate-prompt = Mirror {$mirror} is gone, replace it with
+import-custom-mirror = Adding {$url} as custom mirror {$name}.
+custom-added = Added custom mirror {$name}, run `mirrormgr set --mirror {$name}' or `mirrormgr add --mirrors {$name}' to use it.
+custom-removed = Removed custom mirror {$name}.
+custom-edited = Updated custom mirror {$name}.
+custom-would-add = Would add custom mirror {$name}.
+custom-would-remove = Would remove custom mirror {$name}.
+custom-would-edit = Would update custom mirror {$name}.
+custom-list-empty = No custom mirror is defined.
+import-other-file = {$path} is not managed by mirrormgr and still configures imported mirrors, you may want to remove it.
+restore-to = Restoring configuration {$id} ...
+speedtest-budget-exceeded = Speedtest time budget exceeded, {$count} mirror(s) were not tested.
 
 # error messages
 comp-not-found = The specified component {$comp} does not exist.
@@ -19,11 +68,74 @@ branch-already-enabled = The specified branch {$branch} is already enabled.
 mirror-not-found = Cannot find mirror: {$mirror}. Please use `oma mirror' or `mirrormgr' to select a mirror from the list of available mirrors, or use `oma mirror custom-mirror' to add a custom mirror.
 mirror-already-enabled = The specified mirror {$mirror} is already enabled!
 mirror-already-disabled = The specified mirror {$mirror} is already disabled or does not exist！
+mirror-retired = Mirror {$mirror} has been retired, please choose another one.
+mirror-resolved = Using mirror {$mirror} for {$name}.
+mirror-replaced = Mirror {$old} was renamed or replaced by {$new}, switching to it.
+mirror-retired-enabled = Enabled mirror {$mirror} has been retired, run `mirrormgr sync' to migrate off it.
 mirror-error = Failed to fetch test data from {$mirror}, please check your network connection!
+mirror-untested = {$mirror} was not tested within the time budget.
 custom-parse-failed = Failed to parse custom config: {$custom_path}
+custom-error-at-line = Line {$line}: {$error}
+custom-fix-prompt = What do you want to do?
+custom-reedit = Edit it again
+custom-discard = Discard the changes
+custom-keep-broken = Save it anyway
+custom-discarded = Custom mirror file left unchanged.
+custom-duplicate = Ignoring custom mirror {$name} in {$path}, it is already defined in {$first}.
+custom-name-taken-in = Ignoring custom mirror {$name} in {$path}, a distro mirror has the same name.
+custom-in-dropin = Custom mirror {$name} is defined in {$path}, please change it there.
+custom-invalid-name = Invalid custom mirror name: {$name}. Use only letters, digits, `.', `_' and `-', not starting with `-'.
+custom-name-taken = {$name} is already the name of a distro mirror, please choose another name.
+custom-invalid-url = Invalid mirror URL: {$url}. It must be an absolute http://, https:// or file:// URL.
+custom-exists = Custom mirror {$name} already exists, use `mirrormgr custom edit' to change it.
+custom-exists-in = Custom mirror {$name} is already defined in {$path}.
+custom-not-found = Custom mirror {$name} does not exist.
+custom-edit-nothing = Nothing to change, please pass --url, --desc, --location, --priority or --notes.
+custom-edit-needs-name = Please give the name of the custom mirror to change.
+custom-no-mirror-left = Removing {$mirrors} would leave no mirror enabled, please enable another mirror first.
+custom-renamed-enabled = Enabled mirror {$old} is now {$new}.
+custom-url-updated = Updated URL of enabled mirror {$mirror} to {$url}.
+custom-disabled-removed = Disabled mirror {$mirror}, as it was removed.
+custom-unchanged = No custom mirror was changed.
+custom-rewrite = Could not find {$name} to change it in place, rewriting {$path} without its comments and formatting.
 no-delete-only-mirror = You only have one mirror left, refusing to remove.
 no-delete-only-comp = Refusing to remove essential component "main".
 execute-pkexec-fail = Failed to execute `pkexec': {$e}.
+format-already-set = APT sources format is already {$format}.
+signed-by-already-set = The specified Signed-By keyring is already set.
+no-mirror-fast-enough = No mirror reached the minimum speed of {$speed}.
+not-enough-fast-mirrors = Only {$count} mirror(s) reached the minimum speed of {$speed}.
+save-speedtest-failed = Failed to save speedtest results to {$path}: {$e}
+recover-commit-failed = Failed to finish writing the configuration interrupted in the last run: {$e}
+refresh-failed-rollback = Failed to refresh APT repositories with the new configuration, restoring the previous one ...
+refresh-failed-no-rollback = Failed to refresh APT repositories with the new configuration, keeping it as requested by --no-rollback.
+rollback-failed = Failed to restore the previous configuration.
+nothing-to-undo = There is no earlier configuration to revert to.
+no-such-history-entry = There is no configuration {$id} in the history.
+save-history-failed = Failed to save configuration history to {$path}: {$e}
+state-locked = Another mirrormgr process is changing the mirror configuration.
+apt-locked = {$path} is locked, APT, oma or dpkg is running.
+lock-use-wait = Try again later, or use --wait to wait for it.
+lock-wait-timeout = Timed out waiting for it to be unlocked.
+status-corrupted = The mirrormgr status file {$path} is corrupted.
+no-sources-entries = There is no repository in the current APT sources.
+no-known-mirror-in-sources = None of the repositories in the current APT sources is a known mirror.
+no-branch-for-suites = No branch consists of the suites {$suites} in the current APT sources.
+source-malformed = {$path}:{$line}: can not parse this entry.
+source-unknown-suite = {$location}: suite {$suite} does not belong to any branch, skipping this entry.
+source-unknown-comp = {$location}: unknown component {$comp}, leaving it out.
+drift-sources = {$path} differs from what mirrormgr generates, it was edited by hand or the configuration is out of date.
+drift-legacy-sources = {$path} is left over from the other sources format.
+drift-mirror-not-found = Enabled mirror {$mirror} is no longer in the mirror list.
+drift-mirror-url-changed = URL of mirror {$mirror} changed from {$old} to {$new}.
+drift-mirror-retired = Enabled mirror {$mirror} has been retired.
+drift-mirror-replaced = Enabled mirror {$mirror} was renamed or replaced by {$new}.
+sync-missing-kept = Keeping mirror {$mirror} which is gone, run `mirrormgr sync' in a terminal or with --yes to migrate off it.
+drift-branch-not-found = Branch {$branch} no longer exists.
+drift-comp-not-found = Enabled component {$comp} no longer exists.
+verify-found = Found {$count} problem(s), run `mirrormgr doctor' to fix them.
+doctor-needs-terminal = `mirrormgr doctor' asks before fixing each problem, run it in a terminal or pass --yes.
+status-corrupted-hint = Run `mirrormgr' in a terminal to rebuild it, or `mirrormgr reset' to start over.
 
 # file content
 generated = # Generated by mirrormgr. DO NOT EDIT THIS FILE!
@@ -1,15 +1,64 @@
 # lists
+status-branch = 分支
+status-components = 组件
+status-format = 软件源格式
+status-mirrors = 已启用的镜像源
+speed-record = {$speed}，{$age} 前
+speed-record-failed = 测速失败，{$age} 前
+speed-record-stale = 已过时
+history-age = {$age} 前
+history-initial = （记录历史之前）
+status-rebuild = 根据当前 APT 软件源配置重建
+status-reset = 重置为默认配置
+doctor-regenerate = 使用根据配置生成的软件源覆盖
+doctor-import = 将改动导入配置
+doctor-skip = 保持不变
+sync-disable = 停用
 
 # messages
 disable-comp = 正在禁用 {$comp} 组件 ...
 set-mirror = 正在将 {$mirror} 设定为默认镜像源！
 remove-mirror = 正在从 sources.list 移除 {$mirror} 的镜像源信息 ...
-write-sources = 正在生成 /etc/apt/sources.list ...
+write-sources = 正在生成 APT 软件源配置 ...
 run-refresh = 正在刷新镜像源 ...
 activating-count-mirrors = 正在启用 {$count} 个镜像源
 select-open-or-close-mirrors = 选中镜像源以启用或禁用
 help-message = 按 [Space] 或 [Enter] 启用和禁用镜像源，按 [Esc] 应用更改，按 [Ctrl-c] 退出。
 fix-custom-howto = 这一般是由于语法错误造成的，请尝试检查并编辑上列文件。
+set-format = 正在将 APT 软件源格式设置为 {$format} ...
+remove-legacy-sources = 正在移除旧的 APT 软件源文件 {$path} ...
+dry-run-nothing-written = 试运行模式，未写入任何文件，也未刷新镜像源。
+dry-run-no-changes = 试运行模式，没有需要更改的内容。
+recovered-commit = 已完成上次运行中断的配置写入。
+rolled-back = 已恢复先前的配置。
+undo-to = 正在恢复到配置 {$id} ...
+waiting-for-lock = 正在等待 {$path} 解锁 ...
+status-backup = 已将损坏的状态文件备份到 {$path}。
+status-corrupted-prompt = 要如何恢复状态文件？
+unknown-source-uri = {$uri} 不是已知的镜像源，将忽略。
+verify-ok = 未发现问题。
+doctor-fix-sources = 要如何修复？
+doctor-fix-legacy = 是否删除？
+doctor-fix-mirror = 是否停用镜像源 {$mirror}？
+doctor-fix-branch = 是否切换到分支 {$branch}？
+doctor-fix-comp = 是否停用组件 {$comp}？
+doctor-fixed = 正在应用修复 ...
+doctor-fix-url = 是否更新？
+doctor-fix-replaced = 是否切换到镜像源 {$new}？
+sync-updated-url = 已更新镜像源 {$mirror} 的地址：{$old} -> {$new}
+sync-up-to-date = 所有已启用的镜像源均为最新。
+sync-migrate-prompt = 镜像源 {$mirror} 已不存在，替换为
+import-custom-mirror = 正在将 {$url} 添加为自定义镜像源 {$name}。
+custom-added = 已添加自定义镜像源 {$name}，请运行 `mirrormgr set --mirror {$name}' 或 `mirrormgr add --mirrors {$name}' 以启用。
+custom-removed = 已删除自定义镜像源 {$name}。
+custom-edited = 已更新自定义镜像源 {$name}。
+custom-would-add = 将添加自定义镜像源 {$name}。
+custom-would-remove = 将删除自定义镜像源 {$name}。
+custom-would-edit = 将更新自定义镜像源 {$name}。
+custom-list-empty = 未定义任何自定义镜像源。
+import-other-file = {$path} 不由 mirrormgr 管理，但仍配置了已导入的镜像源，你可能需要删除该文件。
+restore-to = 正在恢复配置 {$id} ...
+speedtest-budget-exceeded = 测速超出时间限制，{$count} 个镜像源未测试。
 
 # error messages
 comp-not-found = 组件 {$comp} 不存在。
@@ -19,11 +68,74 @@ branch-already-enabled = 分支 {$branch} 已经启动
 mirror-not-found = 找不到镜像源：{$mirror} 。请使用 `oma mirror' 或 `mirrormgr' 命令查看源列表并选择源，或使用 `oma mirror custom-mirror' 添加自定义源。
 mirror-already-enabled = 镜像源 {$mirror} 之前已被启用！
 mirror-already-disabled = 镜像源 {$mirror} 之前已被关闭或不存在 ！
+mirror-retired = 镜像源 {$mirror} 已停止服务，请选择其他镜像源。
+mirror-resolved = 使用镜像源 {$mirror} 作为 {$name}。
+mirror-replaced = 镜像源 {$old} 已更名或被 {$new} 取代，正在切换。
+mirror-retired-enabled = 已启用的镜像源 {$mirror} 已停止服务，请运行 `mirrormgr sync' 以迁移。
 mirror-error = 无法从 {$mirror} 下载测试数据，请检查你的网络连接！
+mirror-untested = {$mirror} 未能在时间限制内完成测试。
 custom-parse-failed = 无法解析自定义软件源文件: {$custom_path}
+custom-error-at-line = 第 {$line} 行：{$error}
+custom-fix-prompt = 您希望如何处理？
+custom-reedit = 重新编辑
+custom-discard = 放弃修改
+custom-keep-broken = 仍然保存
+custom-discarded = 自定义镜像源文件未作修改。
+custom-duplicate = 忽略 {$path} 中的自定义镜像源 {$name}，该名称已在 {$first} 中定义。
+custom-name-taken-in = 忽略 {$path} 中的自定义镜像源 {$name}，已有同名的发行版镜像源。
+custom-in-dropin = 自定义镜像源 {$name} 定义于 {$path}，请在该文件中修改。
+custom-invalid-name = 无效的自定义镜像源名称：{$name}。仅可使用字母、数字、`.'、`_' 和 `-'，且不能以 `-' 开头。
+custom-name-taken = {$name} 已是发行版镜像源的名称，请换用其他名称。
+custom-invalid-url = 无效的镜像源地址：{$url}。须为 http://、https:// 或 file:// 开头的完整地址。
+custom-exists = 自定义镜像源 {$name} 已存在，请使用 `mirrormgr custom edit' 修改。
+custom-exists-in = 自定义镜像源 {$name} 已在 {$path} 中定义。
+custom-not-found = 自定义镜像源 {$name} 不存在。
+custom-edit-nothing = 无需修改，请指定 --url、--desc、--location、--priority 或 --notes。
+custom-edit-needs-name = 请指定要修改的自定义镜像源名称。
+custom-no-mirror-left = 删除 {$mirrors} 将导致没有已启用的镜像源，请先启用其他镜像源。
+custom-renamed-enabled = 已启用的镜像源 {$old} 已更名为 {$new}。
+custom-url-updated = 已将已启用的镜像源 {$mirror} 的地址更新为 {$url}。
+custom-disabled-removed = 已停用镜像源 {$mirror}，因其已被删除。
+custom-unchanged = 没有自定义镜像源被修改。
+custom-rewrite = 无法定位 {$name} 以原地修改，将重写 {$path}，其中的注释和格式将丢失。
 no-delete-only-mirror = 无法移除唯一启用的镜像源！
 no-delete-only-comp = 不允许删除必要组件 "main" 。
 execute-pkexec-fail = 无法执行 `pkexec' 命令：{$e}。
+format-already-set = APT 软件源格式已经是 {$format}。
+signed-by-already-set = 指定的 Signed-By 密钥环已经设置。
+no-mirror-fast-enough = 没有镜像源达到最低速度 {$speed}。
+not-enough-fast-mirrors = 仅有 {$count} 个镜像源达到最低速度 {$speed}。
+save-speedtest-failed = 无法将测速结果保存到 {$path}：{$e}
+recover-commit-failed = 无法完成上次运行中断的配置写入：{$e}
+refresh-failed-rollback = 无法使用新配置刷新 APT 软件源，正在恢复先前的配置 ...
+refresh-failed-no-rollback = 无法使用新配置刷新 APT 软件源，已按 --no-rollback 要求保留新配置。
+rollback-failed = 无法恢复先前的配置。
+nothing-to-undo = 没有可以恢复的先前配置。
+no-such-history-entry = 历史记录中没有配置 {$id}。
+save-history-failed = 无法将配置历史保存到 {$path}：{$e}
+state-locked = 另一个 mirrormgr 进程正在修改镜像源配置。
+apt-locked = {$path} 已被锁定，APT、oma 或 dpkg 正在运行。
+lock-use-wait = 请稍后重试，或使用 --wait 等待其完成。
+lock-wait-timeout = 等待解锁超时。
+status-corrupted = mirrormgr 状态文件 {$path} 已损坏。
+no-sources-entries = 当前 APT 软件源配置中没有任何软件源。
+no-known-mirror-in-sources = 当前 APT 软件源配置中没有已知的镜像源。
+no-branch-for-suites = 没有由当前 APT 软件源配置中的 {$suites} 组成的分支。
+source-malformed = {$path}:{$line}：无法解析此条目。
+source-unknown-suite = {$location}：{$suite} 不属于任何分支，跳过此条目。
+source-unknown-comp = {$location}：未知组件 {$comp}，将忽略。
+drift-sources = {$path} 与 mirrormgr 生成的内容不一致，可能被手动修改过或配置已过时。
+drift-legacy-sources = {$path} 是另一种软件源格式的遗留文件。
+drift-mirror-not-found = 已启用的镜像源 {$mirror} 已不在镜像源列表中。
+drift-mirror-url-changed = 镜像源 {$mirror} 的地址已从 {$old} 变更为 {$new}。
+drift-mirror-retired = 已启用的镜像源 {$mirror} 已停止服务。
+drift-mirror-replaced = 已启用的镜像源 {$mirror} 已更名或被 {$new} 取代。
+sync-missing-kept = 保留已不存在的镜像源 {$mirror}，请在终端中运行 `mirrormgr sync' 或使用 --yes 以迁移。
+drift-branch-not-found = 分支 {$branch} 已不存在。
+drift-comp-not-found = 已启用的组件 {$comp} 已不存在。
+verify-found = 发现 {$count} 个问题，请运行 `mirrormgr doctor' 修复。
+doctor-needs-terminal = `mirrormgr doctor' 会在修复每个问题前询问，请在终端中运行或使用 --yes。
+status-corrupted-hint = 请在终端中运行 `mirrormgr' 以重建状态文件，或运行 `mirrormgr reset' 重新开始。
 
 # file content
 generated = # 本文件使用 mirrormgr 生成，请勿编辑！
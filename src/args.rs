@@ -1,4 +1,6 @@
-use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser, Debug)]
 #[clap(about, version, author)]
@@ -7,6 +9,18 @@ pub struct Args {
     pub subcommand: Option<MirrorMgrCommand>,
     #[arg(short, long)]
     pub debug: bool,
+    /// Configure the system installed under this directory (e.g: a chroot or an image being built)
+    #[arg(long, global = true, default_value = "/")]
+    pub root: PathBuf,
+    /// Print the changes to sources and status as a diff, without writing or refreshing
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+    /// Keep the new configuration even if refreshing APT repositories with it fails
+    #[arg(long, global = true)]
+    pub no_rollback: bool,
+    /// Wait up to this many seconds for other mirrormgr, APT or dpkg processes to finish
+    #[arg(long, global = true, value_name = "SECONDS")]
+    pub wait: Option<u64>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -14,7 +28,7 @@ pub enum MirrorMgrCommand {
     /// Set APT repository mirror, branch and components
     Set(Set),
     /// Add APT repository mirror, branch and components
-    Add(NormalArgs),
+    Add(Add),
     /// Remove APT repository mirror, branch and components
     Remove(NormalArgs),
     /// Reset all APT repositories mirror settings
@@ -22,11 +36,131 @@ pub enum MirrorMgrCommand {
     /// Mirrormgr menu
     Menu,
     /// Speedtest mirrors
-    Speedtest,
-    /// Edit custom mirror settings
-    CustomMirrors,
+    Speedtest(Speedtest),
+    /// Manage custom mirrors, or edit them in $EDITOR without a subcommand
+    #[clap(name = "custom", visible_alias = "custom-mirrors")]
+    CustomMirrors(Custom),
     /// Sort Mirror settings
     SortMirrors,
+    /// Show current branch, components and enabled mirrors
+    Status(Status),
+    /// List available mirrors, branches or components
+    List(List),
+    /// Take over the mirrors, branch and components configured in the current APT sources
+    Import,
+    /// Update stored mirror URLs from the mirror list, and migrate off mirrors which are gone
+    Sync(Sync),
+    /// Check the APT sources and the configuration against the mirror, branch and component lists
+    Verify,
+    /// Find and fix problems reported by `verify`
+    Doctor(Doctor),
+    /// Show previously applied configurations
+    History(History),
+    /// Revert the last configuration change
+    Undo,
+}
+
+#[derive(Parser, Debug)]
+pub struct Custom {
+    #[clap(subcommand)]
+    pub action: Option<CustomAction>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CustomAction {
+    /// Add a custom mirror
+    Add {
+        name: String,
+        url: String,
+        #[clap(flatten)]
+        details: CustomMirrorDetails,
+    },
+    /// Remove a custom mirror, disabling it if it is enabled
+    ///
+    /// Only its entry is removed from the custom mirror file. If the entry cannot be found in the
+    /// file as written, e.g. in flow style, the whole file is rewritten without its comments.
+    Remove { name: String },
+    /// List custom mirrors
+    List {
+        /// Print as JSON
+        #[clap(long)]
+        json: bool,
+    },
+    /// Change a custom mirror, or open the custom mirror file in $EDITOR without a name
+    ///
+    /// Only the entry of the mirror is rewritten in the custom mirror file. If it cannot be found
+    /// in the file as written, e.g. in flow style, the whole file is rewritten without its
+    /// comments.
+    Edit {
+        name: Option<String>,
+        /// New URL
+        #[clap(long, requires = "name")]
+        url: Option<String>,
+        #[clap(flatten)]
+        details: CustomMirrorDetails,
+    },
+}
+
+#[derive(Parser, Debug)]
+pub struct CustomMirrorDetails {
+    /// Description shown in the mirror list
+    #[clap(long)]
+    pub desc: Option<String>,
+    /// Where the mirror is, e.g. a city or a network
+    #[clap(long)]
+    pub location: Option<String>,
+    /// Mirrors with a higher priority are listed first
+    #[clap(long, allow_hyphen_values = true)]
+    pub priority: Option<i32>,
+    /// Free-form notes
+    #[clap(long)]
+    pub notes: Option<String>,
+}
+
+impl CustomMirrorDetails {
+    pub fn is_empty(&self) -> bool {
+        self.desc.is_none()
+            && self.location.is_none()
+            && self.priority.is_none()
+            && self.notes.is_none()
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct Sync {
+    /// Disable mirrors which are gone without asking
+    #[clap(short, long)]
+    pub yes: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct Doctor {
+    /// Apply the suggested fix for every problem without asking
+    #[clap(short, long)]
+    pub yes: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct History {
+    #[clap(subcommand)]
+    pub action: Option<HistoryAction>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum HistoryAction {
+    /// Apply an earlier configuration again
+    Restore {
+        /// Entry ID, as shown by `mirrormgr history`
+        id: u64,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    #[value(name = "openmetrics")]
+    OpenMetrics,
 }
 
 #[derive(Parser, Debug)]
@@ -35,9 +169,63 @@ pub struct Set {
     /// Mirror name, e.g: origin
     #[clap(short, long)]
     pub mirror: Option<String>,
+    /// Speedtest mirrors and set the fastest one
+    #[clap(long, conflicts_with = "mirror")]
+    pub fastest: bool,
+    /// Minimum speed (KiB/s) for a mirror to be picked by --fastest
+    #[clap(long, default_value_t = 100.0, requires = "fastest")]
+    pub min_speed: f32,
+    /// Number of mirrors tested at the same time by --fastest
+    #[clap(long, default_value_t = 4, value_parser = clap::value_parser!(u16).range(1..), requires = "fastest")]
+    pub jobs: u16,
+    /// Seconds to wait for each mirror tested by --fastest
+    #[clap(long, default_value_t = 10, requires = "fastest")]
+    pub timeout: u64,
     /// Branch name, e.g: stable
     #[clap(short, long)]
     pub branch: Option<String>,
+    /// APT sources format to write
+    #[clap(short, long, value_enum)]
+    pub format: Option<SourcesFormatArg>,
+    /// Keyring used in the signed-by option of the APT sources, pass an empty string to unset
+    #[clap(long)]
+    pub signed_by: Option<String>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum SourcesFormatArg {
+    /// One-line style /etc/apt/sources.list
+    OneLine,
+    /// deb822 style /etc/apt/sources.list.d/aosc.sources
+    Deb822,
+}
+
+#[derive(Parser, Debug)]
+#[group(required = true)]
+pub struct Add {
+    /// Mirror(s) name, e.g: origin
+    #[clap(short, long)]
+    pub mirrors: Option<Vec<String>>,
+    /// Speedtest mirrors and add the N fastest ones
+    #[clap(
+        long,
+        value_name = "N",
+        conflicts_with = "mirrors",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub fastest: Option<usize>,
+    /// Minimum speed (KiB/s) for a mirror to be picked by --fastest
+    #[clap(long, default_value_t = 100.0, requires = "fastest")]
+    pub min_speed: f32,
+    /// Number of mirrors tested at the same time by --fastest
+    #[clap(long, default_value_t = 4, value_parser = clap::value_parser!(u16).range(1..), requires = "fastest")]
+    pub jobs: u16,
+    /// Seconds to wait for each mirror tested by --fastest
+    #[clap(long, default_value_t = 10, requires = "fastest")]
+    pub timeout: u64,
+    /// component name, e.g: main
+    #[clap(short, long)]
+    pub components: Option<Vec<String>>,
 }
 
 #[derive(Parser, Debug)]
@@ -50,3 +238,52 @@ pub struct NormalArgs {
     #[clap(short, long)]
     pub components: Option<Vec<String>>,
 }
+
+#[derive(Parser, Debug)]
+pub struct Status {
+    /// Print as JSON
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct List {
+    /// What to list
+    #[clap(value_enum)]
+    pub kind: ListKind,
+    /// Print as JSON
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ListKind {
+    Mirrors,
+    Branches,
+    Components,
+}
+
+#[derive(Parser, Debug)]
+pub struct Speedtest {
+    /// Number of mirrors to test at the same time, 1 tests them one by one
+    #[clap(short, long, default_value_t = 4, value_parser = clap::value_parser!(u16).range(1..))]
+    pub jobs: u16,
+    /// Seconds to wait for each mirror, all rounds included
+    #[clap(short, long, default_value_t = 10)]
+    pub timeout: u64,
+    /// Stop testing after this many seconds in total
+    #[clap(long)]
+    pub budget: Option<u64>,
+    /// Download the test file this many times from each mirror
+    #[clap(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    pub rounds: u16,
+    /// Print results to stdout in a machine readable format
+    #[clap(long, conflicts_with = "latency")]
+    pub format: Option<ExportFormat>,
+    /// Only measure connect, connection setup and first byte latency
+    #[clap(long)]
+    pub latency: bool,
+    /// Measure latency first and only test the throughput of the N best mirrors
+    #[clap(long, value_name = "N", conflicts_with = "latency")]
+    pub prefilter: Option<usize>,
+}
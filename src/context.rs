@@ -0,0 +1,294 @@
+use std::{
+    cell::Cell,
+    fs,
+    io::{stderr, stdin, IsTerminal},
+    path::PathBuf,
+    time::Duration,
+};
+
+use dialoguer::{console::Term, theme::ColorfulTheme, Select};
+
+use eyre::{bail, Context as _, Result};
+use oma_console::console;
+use tracing::{info, warn};
+
+use crate::args::Args;
+
+use mirrormgr::{
+    fl,
+    history::History,
+    lock::{check_apt_locks, lock_state, StateLock},
+    mgr::{Branches, Comps, DistroConfig, MirrorManager, MirrorStatus, Mirrors, StatusCorrupted},
+    paths::Paths,
+    transaction,
+    utils::{distro_and_custom_mirrors, refresh},
+};
+
+/// Global options shared by all subcommands.
+pub struct Context {
+    pub paths: Paths,
+    pub dry_run: bool,
+    /// Restore the previous configuration if refreshing fails
+    pub rollback: bool,
+    /// How long to wait for locks held by other processes
+    pub wait: Option<Duration>,
+    /// Problems with custom mirror files were already reported
+    custom_reported: Cell<bool>,
+}
+
+impl Context {
+    pub fn new(args: &Args) -> Self {
+        Self {
+            paths: Paths::new(&args.root),
+            dry_run: args.dry_run,
+            rollback: !args.no_rollback,
+            wait: args.wait.map(Duration::from_secs),
+            custom_reported: Cell::new(false),
+        }
+    }
+
+    /// Distro and custom mirrors. Skipped custom mirror files and entries are reported the first
+    /// time only, as mirrors are often loaded more than once per command.
+    pub fn mirrors(&self) -> Result<Mirrors> {
+        let (mirrors, problems) = distro_and_custom_mirrors(&self.paths)?;
+
+        if !self.custom_reported.replace(true) {
+            for problem in problems {
+                warn!("{problem}");
+            }
+        }
+
+        Ok(mirrors)
+    }
+
+    /// Finish writing the configuration if the last run was interrupted in the middle of it.
+    /// The state lock must be held, so that the journal of a running process is not replayed.
+    fn recover(&self) {
+        if self.dry_run {
+            return;
+        }
+
+        match transaction::recover(self.paths.commit_journal()) {
+            Ok(true) => info!("{}", fl!("recovered-commit")),
+            Ok(false) => {}
+            Err(e) => warn!("{}", fl!("recover-commit-failed", e = format!("{e:#}"))),
+        }
+    }
+
+    /// Keep other mirrormgr processes from changing the configuration until the lock is dropped.
+    /// An interrupted commit is finished once the lock is taken.
+    pub fn lock(&self) -> Result<StateLock> {
+        let lock = lock_state(&self.paths, self.wait, self.dry_run)?;
+        self.recover();
+
+        Ok(lock)
+    }
+
+    /// Read the status file, switching renamed mirrors to their new name and warning about
+    /// retired ones. The switch is only made in memory and is saved with the command's changes.
+    pub fn load_status(&self) -> Result<MirrorManager> {
+        let mut mm = self.read_status()?;
+        let mirrors = self.mirrors()?;
+
+        let replaced = mm.migrate_replaced(&mirrors)?;
+        for (old, new) in &replaced {
+            info!(
+                "{}",
+                fl!("mirror-replaced", old = old.as_str(), new = new.as_str())
+            );
+        }
+
+        self.warn_retired(&mm, &mirrors);
+
+        Ok(mm)
+    }
+
+    /// Warn about enabled mirrors that the mirror list marks as retired.
+    pub fn warn_retired(&self, mm: &MirrorManager, mirrors: &Mirrors) {
+        for mirror in mm.retired_mirrors(mirrors) {
+            warn!("{}", fl!("mirror-retired-enabled", mirror = mirror));
+        }
+    }
+
+    /// Load the status file. If it is corrupted, back it up and let the user rebuild it from
+    /// the current APT sources or reset it, instead of silently starting over. Replaced and
+    /// retired mirrors are left as they are.
+    pub fn read_status(&self) -> Result<MirrorManager> {
+        let path = self.paths.status_file();
+
+        let e = match MirrorManager::new(&path) {
+            Ok(mm) => return Ok(mm),
+            Err(e) if e.downcast_ref::<StatusCorrupted>().is_some() => e,
+            Err(e) => return Err(e),
+        };
+
+        warn!("{e} {}", e.root_cause());
+
+        if !self.dry_run {
+            let mut backup = path.clone().into_os_string();
+            backup.push(".broken");
+            fs::copy(&path, &backup)?;
+            info!(
+                "{}",
+                fl!(
+                    "status-backup",
+                    path = PathBuf::from(backup).display().to_string()
+                )
+            );
+        }
+
+        if !stdin().is_terminal() || !stderr().is_terminal() {
+            return Err(e).context(fl!("status-corrupted-hint"));
+        }
+
+        let choice = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(fl!("status-corrupted-prompt"))
+            .items(&[fl!("status-rebuild"), fl!("status-reset")])
+            .default(0)
+            .interact_on_opt(&Term::stderr())?;
+
+        match choice {
+            Some(0) => {
+                let mirrors = self.mirrors()?;
+                let branches = Branches::from_path(self.paths.branches())?;
+                let comps = Comps::from_path(self.paths.components())?;
+                let import = MirrorStatus::from_sources(&self.paths, &mirrors, &branches, &comps)?;
+
+                for url in import.unknown_mirrors {
+                    warn!("{}", fl!("unknown-source-uri", uri = url));
+                }
+
+                if import.status.mirrors().is_empty() {
+                    bail!(fl!("no-known-mirror-in-sources"));
+                }
+
+                Ok(MirrorManager::with_status(import.status, path))
+            }
+            Some(_) => Ok(MirrorManager::reset(path)),
+            None => Err(e),
+        }
+    }
+
+    /// Write the new configuration and optionally refresh, or only print a diff in dry-run mode.
+    /// The applied configuration is recorded in the history.
+    pub fn apply(&self, mm: &MirrorManager, branches: &Branches, run_refresh: bool) -> Result<()> {
+        self.apply_with_history(mm, branches, run_refresh, |history, status| {
+            let command = std::env::args().collect::<Vec<_>>().join(" ");
+            history.push(command, status.clone());
+        })
+    }
+
+    /// Like [`Context::apply`], updating the history with `update` once the configuration is in place.
+    pub fn apply_with_history<F>(
+        &self,
+        mm: &MirrorManager,
+        branches: &Branches,
+        run_refresh: bool,
+        update: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(&mut History, &MirrorStatus),
+    {
+        if self.dry_run {
+            print_diff(&mm.diff(branches, &self.paths)?);
+            info!("{}", fl!("dry-run-nothing-written"));
+            return Ok(());
+        }
+
+        if run_refresh {
+            check_apt_locks(&self.paths, self.wait)?;
+        }
+
+        let snapshot = if run_refresh && self.rollback {
+            Some(mm.snapshot(&self.paths)?)
+        } else {
+            None
+        };
+
+        let previous = MirrorStatus::from_path(self.paths.status_file()).ok();
+
+        info!("{}", fl!("write-sources"));
+        mm.apply_config(branches, &self.paths)?;
+
+        let res = if run_refresh {
+            info!("{}", fl!("run-refresh"));
+            refresh(&self.paths)
+        } else {
+            Ok(())
+        };
+
+        if let Err(e) = res {
+            match snapshot {
+                Some(snapshot) => {
+                    warn!("{}", fl!("refresh-failed-rollback"));
+                    snapshot
+                        .restore(self.paths.commit_journal())
+                        .context(fl!("rollback-failed"))?;
+                    info!("{}", fl!("rolled-back"));
+                    return Err(e);
+                }
+                None => {
+                    warn!("{}", fl!("refresh-failed-no-rollback"));
+                    self.update_history(previous, mm.status(), update);
+                    return Err(e);
+                }
+            }
+        }
+
+        self.update_history(previous, mm.status(), update);
+
+        Ok(())
+    }
+
+    /// Failing to save the history should not fail an otherwise successful change.
+    fn update_history<F>(&self, previous: Option<MirrorStatus>, status: &MirrorStatus, update: F)
+    where
+        F: FnOnce(&mut History, &MirrorStatus),
+    {
+        let path = self.paths.history();
+        let mut history = History::from_path(&path).unwrap_or_default();
+
+        // So that the first change after upgrading can be undone as well
+        if history.entries().is_empty() {
+            if let Some(previous) = previous {
+                history.push(fl!("history-initial"), previous);
+            }
+        }
+
+        update(&mut history, status);
+
+        if let Err(e) = history.write(&path) {
+            warn!(
+                "{}",
+                fl!(
+                    "save-history-failed",
+                    path = path.display().to_string(),
+                    e = e.to_string()
+                )
+            );
+        }
+    }
+}
+
+pub fn print_diff(diff: &str) {
+    if diff.is_empty() {
+        info!("{}", fl!("dry-run-no-changes"));
+        return;
+    }
+
+    for line in diff.lines() {
+        let line = if line.starts_with("+++") || line.starts_with("---") {
+            console::style(line).bold().to_string()
+        } else if line.starts_with('+') {
+            console::style(line).green().to_string()
+        } else if line.starts_with('-') {
+            console::style(line).red().to_string()
+        } else if line.starts_with("@@") {
+            console::style(line).cyan().to_string()
+        } else {
+            line.to_string()
+        };
+
+        println!("{line}");
+    }
+}
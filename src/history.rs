@@ -0,0 +1,148 @@
+use std::{fs, path::Path, time::Duration};
+
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    mgr::MirrorStatus,
+    transaction::write_atomic,
+    utils::{age, now_secs},
+};
+
+/// Entries older than the last this many are dropped.
+pub const MAX_ENTRIES: usize = 50;
+
+/// A configuration applied in the past.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryEntry {
+    id: u64,
+    timestamp: u64,
+    /// Command line which applied it
+    command: String,
+    status: MirrorStatus,
+}
+
+impl HistoryEntry {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn age(&self) -> Duration {
+        age(self.timestamp)
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    pub fn status(&self) -> &MirrorStatus {
+        &self.status
+    }
+}
+
+/// Applied configurations, oldest first, stored as JSON.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct History {
+    /// IDs are not reused after undoing, so they always refer to the same configuration
+    next_id: u64,
+    entries: Vec<HistoryEntry>,
+}
+
+impl History {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let f = fs::read(path)?;
+        let s = serde_json::from_slice(&f)?;
+
+        Ok(s)
+    }
+
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+
+    pub fn get(&self, id: u64) -> Option<&HistoryEntry> {
+        self.entries.iter().find(|e| e.id == id)
+    }
+
+    pub fn last(&self) -> Option<&HistoryEntry> {
+        self.entries.last()
+    }
+
+    /// Record `status` as applied by `command`, unless it is the same as the last entry.
+    pub fn push(&mut self, command: String, status: MirrorStatus) {
+        if self.last().is_some_and(|e| e.status == status) {
+            return;
+        }
+
+        let id = self.next_id.max(1);
+        self.next_id = id + 1;
+
+        self.entries.push(HistoryEntry {
+            id,
+            timestamp: now_secs(),
+            command,
+            status,
+        });
+
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.drain(..self.entries.len() - MAX_ENTRIES);
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<HistoryEntry> {
+        self.entries.pop()
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        write_atomic(path, &serde_json::to_vec(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(branch: &str) -> MirrorStatus {
+        let mut status = MirrorStatus::default();
+        status.set_branch(branch);
+
+        status
+    }
+
+    #[test]
+    fn push_skips_unchanged() {
+        let mut history = History::default();
+
+        history.push("set --branch stable".to_string(), status("stable"));
+        history.push("set --branch stable".to_string(), status("stable"));
+        history.push("set --branch testing".to_string(), status("testing"));
+
+        let ids = history.entries().iter().map(|e| e.id()).collect::<Vec<_>>();
+        assert_eq!(ids, vec![1, 2]);
+        assert_eq!(history.last().unwrap().command(), "set --branch testing");
+        assert_eq!(history.get(1).unwrap().status(), &status("stable"));
+    }
+
+    #[test]
+    fn push_trims_and_keeps_ids() {
+        let mut history = History::default();
+
+        for i in 0..MAX_ENTRIES + 5 {
+            history.push(format!("run {i}"), status(&i.to_string()));
+        }
+
+        assert_eq!(history.entries().len(), MAX_ENTRIES);
+        assert_eq!(history.entries()[0].id(), 6);
+        assert_eq!(history.last().unwrap().id(), MAX_ENTRIES as u64 + 5);
+        assert!(history.get(5).is_none());
+
+        // IDs of undone entries are not given out again
+        history.pop();
+        history.push("again".to_string(), status("again"));
+        assert_eq!(history.last().unwrap().id(), MAX_ENTRIES as u64 + 6);
+    }
+}
@@ -0,0 +1,50 @@
+//! Manage APT mirrors, branches and components of AOSC OS.
+//!
+//! This is the library behind the `mirrormgr` command. It loads the distro repository data
+//! (`mirrors.yml`, `branches.yml`, `comps.yml`) and custom mirrors, keeps track of the user's
+//! selection in [`mgr::MirrorStatus`], and renders and writes the APT sources from it.
+//!
+//! ```no_run
+//! use mirrormgr::{
+//!     mgr::{Branches, DistroConfig, MirrorManager},
+//!     paths::Paths,
+//!     utils::distro_and_custom_mirrors,
+//! };
+//!
+//! let paths = Paths::new("/mnt/target");
+//! let (mirrors, _problems) = distro_and_custom_mirrors(&paths)?;
+//! let branches = Branches::from_path(paths.branches())?;
+//!
+//! let mut mm = MirrorManager::new(paths.status_file())?;
+//! mm.set_mirror("origin", &mirrors)?;
+//! mm.set_branch("stable", &branches)?;
+//!
+//! println!("{}", mm.try_to_string(&branches, &paths)?);
+//! mm.apply_config(&branches, &paths)?;
+//! # Ok::<(), eyre::Report>(())
+//! ```
+
+pub mod history;
+mod i18n;
+pub mod lock;
+pub mod mgr;
+pub mod paths;
+pub mod sources;
+pub mod speedtest;
+pub mod transaction;
+pub mod utils;
+pub mod verify;
+
+pub use i18n::I18N_LOADER;
+
+pub const STATUS_FILE: &str = "/var/lib/apt/gen/status.json";
+pub const MIRRORS_PATH: &str = "/usr/share/distro-repository-data/mirrors.yml";
+pub const BRANCHES_PATH: &str = "/usr/share/distro-repository-data/branches.yml";
+pub const COMPONENTS_PATH: &str = "/usr/share/distro-repository-data/comps.yml";
+pub const APT_CONFIG: &str = "/etc/apt/sources.list";
+pub const APT_SOURCES_DEB822: &str = "/etc/apt/sources.list.d/aosc.sources";
+pub const CUSTOM_MIRRORS: &str = "/etc/apt-gen-list/custom_mirror.yml";
+pub const CUSTOM_MIRRORS_D: &str = "/etc/apt-gen-list/custom_mirror.d";
+pub const SPEEDTEST_RESULTS: &str = "/var/lib/apt/gen/speedtest.json";
+pub const COMMIT_JOURNAL: &str = "/var/lib/apt/gen/commit.json";
+pub const HISTORY_FILE: &str = "/var/lib/apt/gen/history.json";
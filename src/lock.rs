@@ -0,0 +1,117 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, ErrorKind},
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use eyre::{bail, Context, Result};
+use rustix::fs::{fcntl_lock, flock, FlockOperation};
+use tracing::info;
+
+use crate::{fl, paths::Paths};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Exclusive lock on the mirrormgr state directory, released on drop.
+pub struct StateLock {
+    _dir: Option<File>,
+}
+
+/// Lock the state directory, so that only one mirrormgr changes the configuration at a time.
+///
+/// Without `wait`, fails right away if another process holds the lock. With `dry_run`, the
+/// directory is not created, and nothing is locked if it does not exist yet.
+pub fn lock_state(paths: &Paths, wait: Option<Duration>, dry_run: bool) -> Result<StateLock> {
+    let dir = paths.state_dir();
+
+    if !dry_run {
+        fs::create_dir_all(&dir)?;
+    } else if !dir.is_dir() {
+        return Ok(StateLock { _dir: None });
+    }
+
+    let f = File::open(&dir).with_context(|| format!("Can not open {}", dir.display()))?;
+
+    acquire(&dir, wait, fl!("state-locked"), || {
+        flock(&f, FlockOperation::NonBlockingLockExclusive)
+    })?;
+
+    Ok(StateLock { _dir: Some(f) })
+}
+
+/// Check that neither APT nor dpkg is running, as their lists would be refreshed under them.
+///
+/// The locks are only tested, not kept, so that APT run by the refresh can take them.
+pub fn check_apt_locks(paths: &Paths, wait: Option<Duration>) -> Result<()> {
+    let locks = [
+        paths.apt_lists().join("lock"),
+        paths.resolve("/var/lib/dpkg/lock-frontend"),
+        paths.resolve("/var/lib/dpkg/lock"),
+    ];
+
+    for path in locks {
+        let f = match OpenOptions::new().read(true).write(true).open(&path) {
+            Ok(f) => f,
+            // Nothing ever took this lock
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).context(format!("Can not open {}", path.display())),
+        };
+
+        acquire(
+            &path,
+            wait,
+            fl!("apt-locked", path = path.display().to_string()),
+            || fcntl_lock(&f, FlockOperation::NonBlockingLockExclusive),
+        )?;
+
+        // Closing the file releases the lock
+        drop(f);
+    }
+
+    Ok(())
+}
+
+fn acquire<F>(path: &Path, wait: Option<Duration>, locked: String, mut try_lock: F) -> Result<()>
+where
+    F: FnMut() -> rustix::io::Result<()>,
+{
+    let deadline = wait.map(|w| Instant::now() + w);
+    let mut waiting = false;
+
+    loop {
+        match try_lock() {
+            Ok(()) => return Ok(()),
+            Err(e) if is_busy(e) => {}
+            Err(e) => {
+                return Err(io::Error::from(e)).context(format!("Can not lock {}", path.display()))
+            }
+        }
+
+        let Some(deadline) = deadline else {
+            bail!("{locked} {}", fl!("lock-use-wait"));
+        };
+
+        if Instant::now() >= deadline {
+            bail!("{locked} {}", fl!("lock-wait-timeout"));
+        }
+
+        if !waiting {
+            info!(
+                "{}",
+                fl!("waiting-for-lock", path = path.display().to_string())
+            );
+            waiting = true;
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn is_busy(e: rustix::io::Errno) -> bool {
+    // flock() reports EWOULDBLOCK, fcntl() EAGAIN or EACCES
+    e == rustix::io::Errno::WOULDBLOCK
+        || e == rustix::io::Errno::AGAIN
+        || e == rustix::io::Errno::ACCESS
+}
@@ -1,23 +1,19 @@
 mod args;
-mod i18n;
-mod mgr;
+mod context;
 mod subcmd;
-mod utils;
+
 use args::{Args, MirrorMgrCommand};
 use clap::Parser;
+use context::Context;
 use eyre::Result;
-use i18n::I18N_LOADER;
 use oma_console::OmaLayer;
-use subcmd::{add, custom_mirrors, menu, remove, reset, set, sort_mirrors, speedtest};
+use subcmd::{
+    add, custom_mirrors, doctor, history, import, list, menu, remove, reset, set, sort_mirrors,
+    speedtest, status, sync, undo, verify,
+};
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
 
-const STATUS_FILE: &str = "/var/lib/apt/gen/status.json";
-const MIRRORS_PATH: &str = "/usr/share/distro-repository-data/mirrors.yml";
-const BRANCHES_PATH: &str = "/usr/share/distro-repository-data/branches.yml";
-const COMPONENTS_PATH: &str = "/usr/share/distro-repository-data/comps.yml";
-const APT_CONFIG: &str = "/etc/apt/sources.list";
-const CUSTOM_MIRRORS: &str = "/etc/apt-gen-list/custom_mirror.yml";
 const SPEEDTEST_FILE_CHECKSUM: &str =
     "30e14955ebf1352266dc2ff8067e68104607e750abb9d3b36582b8af909fcb58";
 
@@ -26,19 +22,29 @@ fn main() -> Result<()> {
 
     init_logger(args.debug);
 
+    let ctx = Context::new(&args);
+
     if let Some(subcmd) = args.subcommand {
         match subcmd {
-            MirrorMgrCommand::Set(s) => set::execute(s),
-            MirrorMgrCommand::Add(a) => add::execute(a),
-            MirrorMgrCommand::Remove(a) => remove::execute(a),
-            MirrorMgrCommand::Reset => reset::execute(),
-            MirrorMgrCommand::Menu => menu::execute(),
-            MirrorMgrCommand::Speedtest => speedtest::execute(),
-            MirrorMgrCommand::CustomMirrors => custom_mirrors::execute(),
-            MirrorMgrCommand::SortMirrors => sort_mirrors::execute(),
+            MirrorMgrCommand::Set(s) => set::execute(s, &ctx),
+            MirrorMgrCommand::Add(a) => add::execute(a, &ctx),
+            MirrorMgrCommand::Remove(a) => remove::execute(a, &ctx),
+            MirrorMgrCommand::Reset => reset::execute(&ctx),
+            MirrorMgrCommand::Menu => menu::execute(&ctx),
+            MirrorMgrCommand::Speedtest(s) => speedtest::execute(s, &ctx),
+            MirrorMgrCommand::CustomMirrors(c) => custom_mirrors::execute(c, &ctx),
+            MirrorMgrCommand::SortMirrors => sort_mirrors::execute(&ctx),
+            MirrorMgrCommand::Status(s) => status::execute(s, &ctx),
+            MirrorMgrCommand::List(l) => list::execute(l, &ctx),
+            MirrorMgrCommand::Import => import::execute(&ctx),
+            MirrorMgrCommand::Sync(s) => sync::execute(s, &ctx),
+            MirrorMgrCommand::Verify => verify::execute(&ctx),
+            MirrorMgrCommand::Doctor(d) => doctor::execute(d, &ctx),
+            MirrorMgrCommand::History(h) => history::execute(h, &ctx),
+            MirrorMgrCommand::Undo => undo::execute(&ctx),
         }?;
     } else {
-        menu::execute()?
+        menu::execute(&ctx)?
     }
 
     Ok(())
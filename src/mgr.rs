@@ -2,35 +2,96 @@ use std::{
     collections::HashMap,
     fmt::Display,
     fs::{self, File},
-    io::{Read, Seek, Write},
-    path::Path,
+    io::{ErrorKind, Read},
+    path::{Path, PathBuf},
 };
 
 use eyre::{bail, eyre, Context, Result};
 
 use indexmap::{indexmap, IndexMap};
 use os_release::OsRelease;
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde::{
+    de::{self, value::MapAccessDeserializer, DeserializeOwned, MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use similar::TextDiff;
 use tracing::{info, warn};
+use url::Url;
+
+use crate::{
+    fl,
+    paths::Paths,
+    sources::{mirror_url, read_all},
+    speedtest::{SpeedRecord, SpeedtestResults},
+    transaction::{write_atomic, Snapshot, Transaction},
+    utils::url_strip,
+};
+
+/// Written at the top of a new or rewritten custom mirror file.
+pub const CUSTOM_MIRRORS_HEADER: &str = "\
+# AOSC OS mirrormgr custom mirror config file
+# Usage: custom_mirror_name: URL
+# Like: MY_NAS: https://localhost/aosc
+# After, you can run `mirrormgr set --mirror MY_NAS' to use it.
 
-use crate::{fl, utils::url_strip};
+";
 
+/// Loads, mutates and applies the [`MirrorStatus`] stored in a status file.
 pub struct MirrorManager {
     status: MirrorStatus,
-    status_file: File,
+    status_file: PathBuf,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct MirrorStatus {
+/// The enabled mirrors (in order), branch and components, as stored in status.json.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MirrorStatus {
     branch: String,
     component: Vec<String>,
     mirror: IndexMap<String, String>,
+    #[serde(default)]
+    format: SourcesFormat,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signed_by: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SourcesFormat {
+    /// `deb URL suite comps` lines in /etc/apt/sources.list
+    #[default]
+    OneLine,
+    /// deb822 stanzas in /etc/apt/sources.list.d/aosc.sources
+    Deb822,
+}
+
+impl Display for SourcesFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SourcesFormat::OneLine => "one-line",
+            SourcesFormat::Deb822 => "deb822",
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct MirrorInfo {
     url: String,
     desc: String,
+    /// Where the mirror is, e.g. a city or a network
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    location: Option<String>,
+    /// Mirrors with a higher priority are listed first
+    #[serde(default, skip_serializing_if = "is_zero")]
+    priority: i32,
+    /// The mirror was shut down
+    #[serde(default)]
+    retired: bool,
+    /// The mirror was renamed, or users should move to this one instead
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    replaced_by: Option<String>,
+    /// Other names accepted for this mirror
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    aliases: Vec<String>,
 }
 
 impl MirrorInfo {
@@ -41,39 +102,176 @@ impl MirrorInfo {
     pub fn desc(&self) -> &str {
         &self.desc
     }
+
+    pub fn location(&self) -> Option<&str> {
+        self.location.as_deref()
+    }
+
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    pub fn is_retired(&self) -> bool {
+        self.retired
+    }
+
+    pub fn replaced_by(&self) -> Option<&str> {
+        self.replaced_by.as_deref()
+    }
+
+    pub fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
 }
 
-pub struct Mirror<'a>(&'a str, &'a MirrorInfo);
+pub struct Mirror<'a>(&'a str, &'a MirrorInfo, Option<&'a SpeedRecord>);
 
-impl Mirror<'_> {
+impl<'a> Mirror<'a> {
     pub fn inner(&self) -> (&str, &MirrorInfo) {
         (self.0, self.1)
     }
+
+    /// Show the last speedtest result of this mirror from `results`, if any.
+    pub fn with_speed(self, results: &'a SpeedtestResults) -> Self {
+        let record = results.get(self.0);
+
+        Self(self.0, self.1, record)
+    }
 }
 
 impl Display for Mirror<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(self.1.desc())
+        f.write_str(self.1.desc())?;
+
+        if let Some(record) = self.2 {
+            write!(f, " ({record})")?;
+        }
+
+        Ok(())
     }
 }
 
 #[derive(Serialize, Deserialize)]
-struct BranchInfo {
+pub struct BranchInfo {
     suites: Vec<String>,
 }
 
+impl BranchInfo {
+    pub fn suites(&self) -> &[String] {
+        &self.suites
+    }
+}
+
+/// Branches defined in branches.yml.
 #[derive(Serialize, Deserialize)]
 pub struct Branches(HashMap<String, BranchInfo>);
 
+/// Distro mirrors defined in mirrors.yml, plus custom mirrors once initialized.
 #[derive(Serialize, Deserialize)]
 pub struct Mirrors(HashMap<String, MirrorInfo>);
 
+/// Components defined in comps.yml, mapping names to descriptions.
 #[derive(Serialize, Deserialize)]
 pub struct Comps(HashMap<String, String>);
 
+/// User defined mirrors in custom_mirror.yml, in the order they are written.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct CustomMirrors(pub IndexMap<String, CustomMirror>);
+
+/// A custom mirror, written either as `name: URL` or as a mapping like the distro mirrors.
+/// It is written back in the short form when only the URL is set.
+#[derive(Serialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(into = "CustomMirrorRepr")]
+pub struct CustomMirror {
+    pub url: String,
+    pub desc: Option<String>,
+    pub location: Option<String>,
+    pub priority: i32,
+    /// Free-form notes, not used by mirrormgr
+    pub notes: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum CustomMirrorRepr {
+    Url(String),
+    Full(CustomMirrorFull),
+}
+
 #[derive(Serialize, Deserialize)]
-pub struct CustomMirrors(pub HashMap<String, String>);
+#[serde(deny_unknown_fields)]
+struct CustomMirrorFull {
+    url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    desc: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    location: Option<String>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    priority: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notes: Option<String>,
+}
+
+fn is_zero(x: &i32) -> bool {
+    *x == 0
+}
+
+// Not untagged, so that errors in the long form point at the offending field
+impl<'de> Deserialize<'de> for CustomMirror {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct CustomMirrorVisitor;
+
+        impl<'de> Visitor<'de> for CustomMirrorVisitor {
+            type Value = CustomMirror;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a URL, or a mapping with at least `url'")
+            }
+
+            fn visit_str<E: de::Error>(self, url: &str) -> Result<CustomMirror, E> {
+                Ok(CustomMirror {
+                    url: url.to_string(),
+                    ..Default::default()
+                })
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<CustomMirror, A::Error> {
+                let f = CustomMirrorFull::deserialize(MapAccessDeserializer::new(map))?;
 
+                Ok(CustomMirror {
+                    url: f.url,
+                    desc: f.desc,
+                    location: f.location,
+                    priority: f.priority,
+                    notes: f.notes,
+                })
+            }
+        }
+
+        deserializer.deserialize_any(CustomMirrorVisitor)
+    }
+}
+
+impl From<CustomMirror> for CustomMirrorRepr {
+    fn from(m: CustomMirror) -> Self {
+        if m == (CustomMirror {
+            url: m.url.clone(),
+            ..Default::default()
+        }) {
+            return CustomMirrorRepr::Url(m.url);
+        }
+
+        CustomMirrorRepr::Full(CustomMirrorFull {
+            url: m.url,
+            desc: m.desc,
+            location: m.location,
+            priority: m.priority,
+            notes: m.notes,
+        })
+    }
+}
+
+/// Configuration loaded from a file, YAML unless overridden.
 pub trait DistroConfig: DeserializeOwned {
     fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
         let f = fs::read(path)?;
@@ -139,27 +337,392 @@ impl DistroConfig for CustomMirrors {
     }
 }
 
+/// Custom mirrors merged from the custom mirror file and its drop-ins.
+#[derive(Default)]
+pub struct MergedCustomMirrors {
+    pub mirrors: CustomMirrors,
+    /// File each mirror is defined in
+    pub files: HashMap<String, PathBuf>,
+    /// Files and definitions which were skipped
+    pub problems: Vec<CustomMirrorProblem>,
+}
+
+/// Why a custom mirror file or definition was skipped.
+pub enum CustomMirrorProblem {
+    /// The file could not be read or parsed
+    Unreadable { path: PathBuf, error: eyre::Report },
+    /// An earlier file already defines a custom mirror of this name
+    Duplicate {
+        name: String,
+        path: PathBuf,
+        first: PathBuf,
+    },
+    /// A distro mirror has this name
+    NameTaken { name: String, path: PathBuf },
+}
+
+impl Display for CustomMirrorProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            CustomMirrorProblem::Unreadable { path, error } => format!(
+                "{} {error}",
+                fl!(
+                    "custom-parse-failed",
+                    custom_path = path.display().to_string()
+                )
+            ),
+            CustomMirrorProblem::Duplicate { name, path, first } => fl!(
+                "custom-duplicate",
+                name = name.as_str(),
+                path = path.display().to_string(),
+                first = first.display().to_string()
+            ),
+            CustomMirrorProblem::NameTaken { name, path } => fl!(
+                "custom-name-taken-in",
+                name = name.as_str(),
+                path = path.display().to_string()
+            ),
+        };
+
+        f.write_str(&s)
+    }
+}
+
+/// Names of custom mirrors added, removed or changed by an edit.
+#[derive(Debug, Default)]
+pub struct CustomMirrorsDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl CustomMirrorsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl CustomMirrors {
+    /// Read custom_mirror.yml, then the `*.yml` files of custom_mirror.d in lexical order. The
+    /// first definition of a name wins. Files which can not be parsed and names of `distro`
+    /// mirrors are skipped.
+    pub fn load_all(paths: &Paths, distro: &Mirrors) -> MergedCustomMirrors {
+        Self::load_all_with(paths, distro, None)
+    }
+
+    /// Like [`CustomMirrors::load_all`], using `main` instead of reading custom_mirror.yml.
+    pub fn load_all_with(
+        paths: &Paths,
+        distro: &Mirrors,
+        mut main: Option<Self>,
+    ) -> MergedCustomMirrors {
+        let mut res = MergedCustomMirrors::default();
+        let mut files = vec![paths.custom_mirrors()];
+
+        let dir = paths.custom_mirrors_d();
+        match fs::read_dir(&dir) {
+            Ok(d) => {
+                let mut dropins = d
+                    .filter_map(|e| e.ok())
+                    .map(|e| e.path())
+                    .filter(|p| p.extension().is_some_and(|x| x == "yml") && p.is_file())
+                    .collect::<Vec<_>>();
+                dropins.sort();
+                files.extend(dropins);
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => res.problems.push(CustomMirrorProblem::Unreadable {
+                path: dir,
+                error: e.into(),
+            }),
+        }
+
+        for (i, file) in files.into_iter().enumerate() {
+            let custom = match main.take().filter(|_| i == 0) {
+                Some(custom) => Ok(custom),
+                None => Self::load(&file),
+            };
+
+            let custom = match custom {
+                Ok(custom) => custom,
+                Err(error) => {
+                    res.problems
+                        .push(CustomMirrorProblem::Unreadable { path: file, error });
+                    continue;
+                }
+            };
+
+            for (name, mirror) in custom.0 {
+                if distro.has(&name) {
+                    res.problems.push(CustomMirrorProblem::NameTaken {
+                        name,
+                        path: file.clone(),
+                    });
+                    continue;
+                }
+
+                if let Some(first) = res.files.get(&name) {
+                    res.problems.push(CustomMirrorProblem::Duplicate {
+                        name,
+                        path: file.clone(),
+                        first: first.clone(),
+                    });
+                    continue;
+                }
+
+                res.files.insert(name.clone(), file.clone());
+                res.mirrors.0.insert(name, mirror);
+            }
+        }
+
+        res
+    }
+
+    /// Read the custom mirror file, which may be missing or only contain comments.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::parse(&read_or_empty(path.as_ref())?)
+    }
+
+    /// Parse the content of a custom mirror file, which may only contain comments.
+    pub fn parse(s: &str) -> Result<Self> {
+        let res: Option<Self> = serde_yaml::from_str(s)?;
+
+        Ok(res.unwrap_or_default())
+    }
+
+    /// Render the custom mirror file, with the usage header as the only comment.
+    pub fn to_yaml(&self) -> Result<String> {
+        let mut s = CUSTOM_MIRRORS_HEADER.to_string();
+
+        if !self.0.is_empty() {
+            s.push_str(&serde_yaml::to_string(&self.0)?);
+        }
+
+        Ok(s)
+    }
+
+    /// Write the custom mirror file, replacing comments with the usage header.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        write_atomic(path, self.to_yaml()?.as_bytes())
+    }
+
+    /// Replace the entry `name` of the custom mirror file `s` with `mirror`, or remove it if
+    /// `mirror` is `None`, leaving the rest of the file as it is. Returns `None` if the entry is
+    /// not found, e.g. as the file is written in flow style.
+    pub fn replace_entry(
+        s: &str,
+        name: &str,
+        mirror: Option<&CustomMirror>,
+    ) -> Result<Option<String>> {
+        let lines = s.split_inclusive('\n').collect::<Vec<_>>();
+
+        let Some(start) = lines
+            .iter()
+            .position(|l| top_level_key(l).is_some_and(|k| k == name))
+        else {
+            return Ok(None);
+        };
+
+        // Everything indented below the key, but not the comments before the next entry
+        let mut end = lines[start + 1..]
+            .iter()
+            .position(|l| !l.trim().is_empty() && !l.starts_with([' ', '\t']))
+            .map_or(lines.len(), |i| start + 1 + i);
+
+        while end > start + 1
+            && (lines[end - 1].trim().is_empty() || lines[end - 1].starts_with('#'))
+        {
+            end -= 1;
+        }
+
+        let mut res = lines[..start].concat();
+
+        if let Some(mirror) = mirror {
+            res.push_str(&serde_yaml::to_string(&indexmap! { name => mirror })?);
+        }
+
+        res.push_str(&lines[end..].concat());
+
+        Ok(Some(res))
+    }
+
+    /// Line number, starting at 1, where the entry `name` of the custom mirror file `s` starts.
+    pub fn line_of(s: &str, name: &str) -> Option<usize> {
+        s.lines()
+            .position(|l| top_level_key(l).is_some_and(|k| k == name))
+            .map(|i| i + 1)
+    }
+
+    /// Names added, removed and changed in `new` compared to `self`.
+    pub fn diff(&self, new: &CustomMirrors) -> CustomMirrorsDiff {
+        let mut res = CustomMirrorsDiff::default();
+
+        for (name, mirror) in &self.0 {
+            match new.0.get(name) {
+                None => res.removed.push(name.clone()),
+                Some(m) if m != mirror => res.changed.push(name.clone()),
+                Some(_) => {}
+            }
+        }
+
+        res.added = new
+            .0
+            .keys()
+            .filter(|name| !self.0.contains_key(*name))
+            .cloned()
+            .collect();
+
+        res
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CustomMirror> {
+        self.0.get(name)
+    }
+
+    /// Check that `name` can be used for a custom mirror, i.e. it is made of letters, digits,
+    /// `.`, `_` and `-`, and is not taken by a distro mirror.
+    pub fn validate_name(name: &str, distro: &Mirrors) -> Result<()> {
+        let valid = name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+
+        if name.is_empty() || name.starts_with('-') || !valid {
+            bail!(fl!("custom-invalid-name", name = name));
+        }
+
+        if distro.resolve(name).is_some() {
+            bail!(fl!("custom-name-taken", name = name));
+        }
+
+        Ok(())
+    }
+
+    /// Check that `url` is an absolute http, https or file URL.
+    pub fn validate_url(url: &str) -> Result<()> {
+        let valid = Url::parse(url).is_ok_and(|u| match u.scheme() {
+            "http" | "https" => u.has_host(),
+            "file" => true,
+            _ => false,
+        });
+
+        if !valid {
+            bail!(fl!("custom-invalid-url", url = url));
+        }
+
+        Ok(())
+    }
+
+    /// The custom mirror file `s` with `mirrors` added at the end, keeping its content and
+    /// comments.
+    pub fn appended(s: &str, mirrors: &[(String, CustomMirror)]) -> Result<String> {
+        let mut s = s.to_string();
+
+        if !s.is_empty() && !s.ends_with('\n') {
+            s.push('\n');
+        }
+
+        if s.is_empty() {
+            s.push_str(CUSTOM_MIRRORS_HEADER);
+        }
+
+        for (name, mirror) in mirrors {
+            s.push_str(&serde_yaml::to_string(&indexmap! { name => mirror })?);
+        }
+
+        Ok(s)
+    }
+}
+
+impl Branches {
+    pub fn get(&self, branch: &str) -> Option<&BranchInfo> {
+        self.0.get(branch)
+    }
+
+    pub fn list_branches(&self) -> Vec<(&str, &BranchInfo)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v)).collect()
+    }
+}
+
+impl Comps {
+    pub fn list_comps(&self) -> Vec<(&str, &str)> {
+        self.0
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect()
+    }
+}
+
 impl Mirrors {
-    pub fn list_mirrors(&self) -> Vec<Mirror> {
+    pub fn get(&self, mirror: &str) -> Option<&MirrorInfo> {
+        self.0.get(mirror)
+    }
+
+    /// Mirrors which can be enabled, i.e. neither retired nor replaced, by descending priority
+    /// then name.
+    pub fn list_mirrors(&self) -> Vec<Mirror<'_>> {
         let mut res = vec![];
         for (k, v) in &self.0 {
-            res.push(Mirror(k.as_str(), v));
+            if v.retired || v.replaced_by.is_some() {
+                continue;
+            }
+            res.push(Mirror(k.as_str(), v, None));
         }
 
+        res.sort_unstable_by(|a, b| b.1.priority.cmp(&a.1.priority).then(a.0.cmp(b.0)));
+
         res
     }
 
+    /// Name of the mirror `name` refers to, either directly, as an alias or through
+    /// `replaced_by`.
+    pub fn resolve<'a>(&'a self, name: &'a str) -> Option<&'a str> {
+        let name = match self.0.get_key_value(name) {
+            Some((k, _)) => k.as_str(),
+            None => self
+                .0
+                .iter()
+                .find(|(_, v)| v.aliases.iter().any(|a| a == name))
+                .map(|(k, _)| k.as_str())?,
+        };
+
+        Some(self.replacement(name).unwrap_or(name))
+    }
+
+    /// The mirror which replaces `name`, following `replaced_by` until a mirror which is not
+    /// replaced itself. `None` if `name` is not replaced, or the chain is broken or loops.
+    pub fn replacement(&self, name: &str) -> Option<&str> {
+        let mut current = self.0.get_key_value(name)?;
+        let mut seen = vec![name];
+
+        while let Some(next) = &current.1.replaced_by {
+            if seen.contains(&next.as_str()) {
+                return None;
+            }
+            seen.push(next);
+            current = self.0.get_key_value(next)?;
+        }
+
+        (current.0 != name).then_some(current.0.as_str())
+    }
+
     pub fn init_custom_mirrors(&mut self, c: CustomMirrors) -> Result<()> {
         for (k, v) in c.0 {
             if self.0.contains_key(&k) {
                 bail!("Distro mirror file contains {k}.");
             }
 
+            let desc = v
+                .desc
+                .unwrap_or_else(|| format!("[Custom mirror] {}", v.url));
+
             self.0.insert(
                 k,
                 MirrorInfo {
-                    url: v.clone(),
-                    desc: format!("[Custom mirror] {v}"),
+                    url: v.url,
+                    desc,
+                    location: v.location,
+                    priority: v.priority,
+                    ..Default::default()
                 },
             );
         }
@@ -174,11 +737,34 @@ impl Default for MirrorStatus {
             branch: "stable".to_string(),
             component: vec!["main".to_string()],
             mirror: indexmap! { "origin".to_string() => "https://repo.aosc.io".to_string() },
+            format: SourcesFormat::default(),
+            signed_by: None,
         }
     }
 }
 
 impl MirrorStatus {
+    pub fn branch(&self) -> &str {
+        &self.branch
+    }
+
+    pub fn components(&self) -> &[String] {
+        &self.component
+    }
+
+    /// Enabled mirrors and their URLs, highest priority first.
+    pub fn mirrors(&self) -> &IndexMap<String, String> {
+        &self.mirror
+    }
+
+    pub fn format(&self) -> SourcesFormat {
+        self.format
+    }
+
+    pub fn signed_by(&self) -> Option<&str> {
+        self.signed_by.as_deref()
+    }
+
     pub fn set_mirror(&mut self, mirror: &str, url: String) {
         self.mirror.clear();
         self.add_mirror(mirror, url);
@@ -216,6 +802,49 @@ impl MirrorStatus {
         Ok(false)
     }
 
+    /// Put `new` in place of `old`, or only disable `old` if `new` is already enabled.
+    pub fn replace_mirror(&mut self, old: &str, new: &str, url: String) -> bool {
+        let Some(index) = self.mirror.get_index_of(old) else {
+            return false;
+        };
+
+        if new != old && self.has(new) {
+            self.mirror.shift_remove(old);
+            return true;
+        }
+
+        self.mirror.shift_remove_index(index);
+        self.mirror.shift_insert(index, new.to_owned(), url);
+
+        true
+    }
+
+    /// Update stored URLs of enabled mirrors to the ones in `mirrors`.
+    pub fn sync_urls(&mut self, mirrors: &Mirrors) -> SyncReport {
+        let mut report = SyncReport::default();
+
+        for (name, url) in self.mirror.iter_mut() {
+            let Some(info) = mirrors.0.get(name) else {
+                report.missing.push(name.clone());
+                continue;
+            };
+
+            if info.retired {
+                report.retired.push(name.clone());
+                continue;
+            }
+
+            if url_strip(url) != url_strip(&info.url) {
+                report
+                    .updated
+                    .push((name.clone(), url.clone(), info.url.clone()));
+                *url = info.url.clone();
+            }
+        }
+
+        report
+    }
+
     pub fn add_component(&mut self, comp: String) -> bool {
         let pos = self.component.iter().position(|x| x == &comp);
         if pos.is_none() {
@@ -247,52 +876,249 @@ impl MirrorStatus {
         true
     }
 
-    pub fn write_config(&self, status_file: &File) -> Result<()> {
-        let mut status_file = status_file;
-        // 1. 先把文件大小设置为 0
-        status_file.set_len(0)?;
-        // 2. set_len 并不会调整指针的位置，因此光标的位置可能在意外的位置
-        // 所以需要 reset 到开始的地方
-        status_file.rewind()?;
-        let s = serde_json::to_vec(self)?;
-        status_file.write_all(&s)?;
+    pub fn set_format(&mut self, format: SourcesFormat) -> bool {
+        if self.format == format {
+            return false;
+        }
 
-        Ok(())
+        self.format = format;
+
+        true
+    }
+
+    pub fn set_signed_by(&mut self, signed_by: Option<String>) -> bool {
+        if self.signed_by == signed_by {
+            return false;
+        }
+
+        self.signed_by = signed_by;
+
+        true
+    }
+
+    pub fn write_config<P: AsRef<Path>>(&self, status_file: P) -> Result<()> {
+        write_atomic(status_file, &serde_json::to_vec(self)?)
     }
 
     pub fn list_enabled_mirrors(&self) -> Vec<&str> {
         self.mirror.keys().map(|x| x.as_str()).collect()
     }
+
+    /// Work out the status from the APT sources currently in place: sources.list and all files
+    /// in sources.list.d.
+    ///
+    /// Mirrors not in `mirrors` are left out of the status and reported in
+    /// [`SourcesImport::unknown_mirrors`], entries which can not be used at all in
+    /// [`SourcesImport::unmapped`].
+    pub fn from_sources(
+        paths: &Paths,
+        mirrors: &Mirrors,
+        branches: &Branches,
+        comps: &Comps,
+    ) -> Result<SourcesImport> {
+        let files = read_all(paths)?;
+
+        if files.iter().all(|(_, s)| s.entries.is_empty()) {
+            bail!(fl!("no-sources-entries"));
+        }
+
+        let deb822_path = paths.apt_sources_deb822();
+        let format = if files
+            .iter()
+            .any(|(path, s)| *path == deb822_path && !s.entries.is_empty())
+        {
+            SourcesFormat::Deb822
+        } else {
+            SourcesFormat::OneLine
+        };
+
+        let mut mirror = IndexMap::new();
+        let mut unknown_mirrors = vec![];
+        let mut unmapped = vec![];
+        let mut other_files = vec![];
+        let mut suites: Vec<&str> = vec![];
+        let mut components: Vec<String> = vec![];
+        let mut signed_by = None;
+
+        for (path, sources) in &files {
+            let path_str = path.display().to_string();
+
+            for line in &sources.malformed {
+                unmapped.push(fl!(
+                    "source-malformed",
+                    path = path_str.as_str(),
+                    line = line
+                ));
+            }
+
+            for entry in &sources.entries {
+                let location = format!("{}:{}", path_str, entry.line);
+
+                if let Some(suite) = entry
+                    .suites
+                    .iter()
+                    .find(|s| !branches.0.values().any(|b| b.suites.contains(s)))
+                {
+                    unmapped.push(fl!(
+                        "source-unknown-suite",
+                        location = location,
+                        suite = suite.as_str()
+                    ));
+                    continue;
+                }
+
+                for uri in &entry.uris {
+                    let url = mirror_url(uri);
+                    let known = mirrors
+                        .0
+                        .iter()
+                        .find(|(_, info)| url_strip(&info.url) == url.as_str());
+
+                    match known {
+                        Some((name, info)) => {
+                            mirror.entry(name.clone()).or_insert(info.url.clone());
+                        }
+                        None if !unknown_mirrors.contains(&url) => unknown_mirrors.push(url),
+                        None => {}
+                    }
+                }
+
+                for comp in &entry.components {
+                    if !comps.0.contains_key(comp) {
+                        unmapped.push(fl!(
+                            "source-unknown-comp",
+                            location = location.as_str(),
+                            comp = comp.as_str()
+                        ));
+                    } else if !components.contains(comp) {
+                        components.push(comp.clone());
+                    }
+                }
+
+                for suite in &entry.suites {
+                    if !suites.contains(&suite.as_str()) {
+                        suites.push(suite);
+                    }
+                }
+
+                if signed_by.is_none() {
+                    signed_by = entry.signed_by.clone();
+                }
+
+                if *path != paths.apt_config()
+                    && *path != deb822_path
+                    && !other_files.contains(path)
+                {
+                    other_files.push(path.clone());
+                }
+            }
+        }
+
+        if components.is_empty() {
+            components.push("main".to_string());
+        }
+
+        // The branch made of exactly these suites, or else the smallest one containing them
+        let branch = branches
+            .0
+            .iter()
+            .filter(|(_, info)| suites.iter().all(|s| info.suites.iter().any(|x| x == s)))
+            .min_by_key(|(name, info)| (info.suites.len(), name.as_str()))
+            .map(|(name, _)| name.clone())
+            .ok_or_else(|| eyre!(fl!("no-branch-for-suites", suites = suites.join(" "))))?;
+
+        let status = Self {
+            branch,
+            component: components,
+            mirror,
+            format,
+            signed_by,
+        };
+
+        Ok(SourcesImport {
+            status,
+            unknown_mirrors,
+            unmapped,
+            other_files,
+        })
+    }
+}
+
+/// Status worked out from the current APT sources by [`MirrorStatus::from_sources`].
+pub struct SourcesImport {
+    pub status: MirrorStatus,
+    /// Mirror URLs which do not belong to any known mirror, in the order they appear
+    pub unknown_mirrors: Vec<String>,
+    /// Why some entries or parts of them could not be used
+    pub unmapped: Vec<String>,
+    /// Files besides the ones managed by mirrormgr which contain imported entries
+    pub other_files: Vec<PathBuf>,
+}
+
+/// What [`MirrorStatus::sync_urls`] changed or could not find.
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    /// Name, old URL and new URL of each updated mirror
+    pub updated: Vec<(String, String, String)>,
+    /// Enabled mirrors which no longer exist
+    pub missing: Vec<String>,
+    /// Enabled mirrors which were shut down
+    pub retired: Vec<String>,
+}
+
+/// The status file exists, but can not be parsed.
+#[derive(Debug)]
+pub struct StatusCorrupted(pub PathBuf);
+
+impl Display for StatusCorrupted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&fl!(
+            "status-corrupted",
+            path = self.0.display().to_string()
+        ))
+    }
 }
 
 impl MirrorManager {
-    pub fn new(status_file: File) -> Self {
-        let status = MirrorStatus::from_file(&status_file).unwrap_or_default();
+    /// Load `status_file`, or start from the default status if it does not exist yet.
+    ///
+    /// A status file which can not be parsed is an error with a [`StatusCorrupted`] context,
+    /// rather than being replaced by the default.
+    pub fn new<P: AsRef<Path>>(status_file: P) -> Result<Self> {
+        let status_file = status_file.as_ref();
+
+        let status = match fs::read(status_file) {
+            Ok(s) => {
+                serde_json::from_slice(&s).wrap_err(StatusCorrupted(status_file.to_path_buf()))?
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => MirrorStatus::default(),
+            Err(e) => {
+                return Err(e).context(format!("Can not read {}", status_file.display()));
+            }
+        };
 
-        Self {
-            status,
-            status_file,
-        }
+        Ok(Self::with_status(status, status_file))
     }
 
-    pub fn reset(status_file: File) -> Self {
-        let status = MirrorStatus::default();
+    pub fn reset<P: AsRef<Path>>(status_file: P) -> Self {
+        Self::with_status(MirrorStatus::default(), status_file)
+    }
 
+    /// Manage an existing `status`, which will be written to `status_file` when applied.
+    pub fn with_status<P: AsRef<Path>>(status: MirrorStatus, status_file: P) -> Self {
         Self {
             status,
-            status_file,
+            status_file: status_file.as_ref().to_path_buf(),
         }
     }
 
-    pub fn set_mirror(&mut self, set_mirror: &str, mirrors: &Mirrors) -> Result<()> {
-        let entry = mirrors.0.get(set_mirror);
-
-        if entry.is_none() {
-            bail!(fl!("mirror-not-found", mirror = set_mirror));
-        }
+    pub fn status(&self) -> &MirrorStatus {
+        &self.status
+    }
 
-        self.status
-            .set_mirror(set_mirror, entry.unwrap().url.clone());
+    pub fn set_mirror(&mut self, set_mirror: &str, mirrors: &Mirrors) -> Result<()> {
+        let (name, info) = resolve_enableable(mirrors, set_mirror)?;
+        self.status.set_mirror(name, info.url.clone());
 
         Ok(())
     }
@@ -301,15 +1127,149 @@ impl MirrorManager {
         self.status.reorder_mirrors(mirrors);
     }
 
-    pub fn add_mirrors(&mut self, mirrors: &Mirrors, add_mirrors: &[&str]) -> Result<()> {
-        for m in add_mirrors {
-            let entry = mirrors.0.get(m.to_owned());
+    /// Put mirror `new` in place of `old`, keeping the order of enabled mirrors.
+    pub fn replace_mirror(&mut self, old: &str, new: &str, mirrors: &Mirrors) -> Result<()> {
+        let Some(info) = mirrors.0.get(new) else {
+            bail!(fl!("mirror-not-found", mirror = new));
+        };
+
+        if !self.status.replace_mirror(old, new, info.url.clone()) {
+            warn!("{}", fl!("mirror-already-disabled", mirror = old));
+        }
+
+        Ok(())
+    }
+
+    pub fn sync_urls(&mut self, mirrors: &Mirrors) -> SyncReport {
+        self.status.sync_urls(mirrors)
+    }
+
+    /// Follow changes of custom mirrors in the enabled mirrors, keeping their order. Changed
+    /// ones get their new URL, and removed ones are renamed to an added mirror with the same URL
+    /// or disabled. `mirrors` must already include the new custom mirrors.
+    ///
+    /// Refuses to disable every enabled mirror. Returns whether the status changed.
+    pub fn reconcile_custom(
+        &mut self,
+        diff: &CustomMirrorsDiff,
+        mirrors: &Mirrors,
+    ) -> Result<bool> {
+        let enabled = |name: &String| self.status.mirror.contains_key(name);
+        let removed = diff
+            .removed
+            .iter()
+            .filter(|m| enabled(m))
+            .collect::<Vec<_>>();
+        let changed = diff
+            .changed
+            .iter()
+            .filter(|m| enabled(m))
+            .collect::<Vec<_>>();
+
+        let mut renames = vec![];
+        let mut disabled = vec![];
+
+        for old in removed {
+            let url = url_strip(&self.status.mirror[old]);
+            let new = diff.added.iter().find(|new| {
+                mirrors
+                    .get(new)
+                    .is_some_and(|info| url_strip(&info.url) == url)
+            });
+
+            match new {
+                Some(new) => renames.push((old, new)),
+                None => disabled.push(old),
+            }
+        }
+
+        if !disabled.is_empty() && disabled.len() == self.status.mirror.len() {
+            bail!(fl!(
+                "custom-no-mirror-left",
+                mirrors = disabled
+                    .iter()
+                    .map(|x| x.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
 
-            if entry.is_none() {
-                bail!(fl!("mirror-not-found", mirror = m.to_string()));
+        for (old, new) in &renames {
+            self.replace_mirror(old, new, mirrors)?;
+            info!(
+                "{}",
+                fl!(
+                    "custom-renamed-enabled",
+                    old = old.as_str(),
+                    new = new.as_str()
+                )
+            );
+        }
+
+        let mut updated = false;
+
+        for name in &changed {
+            let Some(info) = mirrors.get(name) else {
+                continue;
+            };
+
+            if url_strip(&self.status.mirror[*name]) != url_strip(&info.url) {
+                // Same key, so it keeps its position
+                self.status
+                    .mirror
+                    .insert(name.to_string(), info.url.clone());
+                updated = true;
+                info!(
+                    "{}",
+                    fl!(
+                        "custom-url-updated",
+                        mirror = name.as_str(),
+                        url = info.url.as_str()
+                    )
+                );
             }
+        }
 
-            let res = self.status.add_mirror(m, entry.unwrap().url.clone());
+        for name in &disabled {
+            self.status.mirror.shift_remove(*name);
+            info!("{}", fl!("custom-disabled-removed", mirror = name.as_str()));
+        }
+
+        Ok(!renames.is_empty() || !disabled.is_empty() || updated)
+    }
+
+    /// Switch enabled mirrors which were renamed or replaced to their replacement, returning
+    /// the (old, new) names.
+    pub fn migrate_replaced(&mut self, mirrors: &Mirrors) -> Result<Vec<(String, String)>> {
+        let replaced = self
+            .status
+            .mirror
+            .keys()
+            .filter_map(|m| Some((m.clone(), mirrors.replacement(m)?.to_string())))
+            .collect::<Vec<_>>();
+
+        for (old, new) in &replaced {
+            self.replace_mirror(old, new, mirrors)?;
+        }
+
+        Ok(replaced)
+    }
+
+    /// Enabled mirrors which were shut down without a replacement.
+    pub fn retired_mirrors(&self, mirrors: &Mirrors) -> Vec<&str> {
+        self.status
+            .mirror
+            .keys()
+            .filter(|m| mirrors.get(m).is_some_and(|info| info.retired))
+            .filter(|m| mirrors.replacement(m).is_none())
+            .map(|m| m.as_str())
+            .collect()
+    }
+
+    pub fn add_mirrors(&mut self, mirrors: &Mirrors, add_mirrors: &[&str]) -> Result<()> {
+        for m in add_mirrors {
+            let (m, info) = resolve_enableable(mirrors, m)?;
+            let res = self.status.add_mirror(m, info.url.clone());
 
             info!("{}", fl!("set-mirror", mirror = m.to_string()));
 
@@ -383,7 +1343,27 @@ impl MirrorManager {
         Ok(())
     }
 
-    pub fn try_to_string(&self, branches: &Branches) -> Result<String> {
+    /// Returns whether the format changed, warning if it was already `format`.
+    pub fn set_format(&mut self, format: SourcesFormat) -> bool {
+        let res = self.status.set_format(format);
+
+        if !res {
+            warn!("{}", fl!("format-already-set", format = format.to_string()));
+        }
+
+        res
+    }
+
+    pub fn set_signed_by(&mut self, signed_by: Option<String>) {
+        let res = self.status.set_signed_by(signed_by);
+
+        if !res {
+            warn!("{}", fl!("signed-by-already-set"));
+        }
+    }
+
+    /// Render the APT sources for the current status in the selected format.
+    pub fn try_to_string(&self, branches: &Branches, paths: &Paths) -> Result<String> {
         let mut s = String::new();
         let branches = &branches
             .0
@@ -392,34 +1372,385 @@ impl MirrorManager {
             .suites;
 
         let components = self.status.component.join(" ");
+        let dir = match OsRelease::new_from(paths.os_release())?.name.as_str() {
+            "AOSC OS" => "debs",
+            "AOSC OS/Retro" | "Afterglow" => "debs-retro",
+            _ => "",
+        };
+
+        match self.status.format {
+            SourcesFormat::OneLine => {
+                let options = match &self.status.signed_by {
+                    Some(signed_by) => format!("[signed-by={signed_by}] "),
+                    None => String::new(),
+                };
+
+                for (_, url) in &self.status.mirror {
+                    for branch in branches {
+                        let url = url_strip(url);
+                        let entry = format!("deb {options}{url}{dir} {branch} {components}\n");
+                        s.push_str(&entry);
+                    }
+                }
+            }
+            SourcesFormat::Deb822 => {
+                let suites = branches.join(" ");
 
-        for (_, url) in &self.status.mirror {
-            for branch in branches {
-                let url = url_strip(url);
-                let entry = format!(
-                    "deb {url}{} {branch} {components}\n",
-                    match OsRelease::new()?.name.as_str() {
-                        "AOSC OS" => "debs",
-                        "AOSC OS/Retro" | "Afterglow" => "debs-retro",
-                        _ => "",
+                for (_, url) in &self.status.mirror {
+                    if !s.is_empty() {
+                        s.push('\n');
                     }
-                );
-                s.push_str(&entry);
+
+                    let url = url_strip(url);
+                    s.push_str("Types: deb\n");
+                    s.push_str(&format!("URIs: {url}{dir}\n"));
+                    s.push_str(&format!("Suites: {suites}\n"));
+                    s.push_str(&format!("Components: {components}\n"));
+
+                    if let Some(signed_by) = &self.status.signed_by {
+                        s.push_str(&format!("Signed-By: {signed_by}\n"));
+                    }
+                }
             }
         }
 
         Ok(s)
     }
 
-    pub fn apply_config<P: AsRef<Path>>(&self, branches: &Branches, apt_path: P) -> Result<()> {
-        self.status.write_config(&self.status_file)?;
-        let res = self.try_to_string(branches)?;
-        fs::write(apt_path, res).context("Can not write apt config")?;
+    /// Returns the sources file to write and the one of the other format to clean up.
+    pub(crate) fn sources_paths(&self, paths: &Paths) -> (PathBuf, PathBuf) {
+        match self.status.format {
+            SourcesFormat::OneLine => (paths.apt_config(), paths.apt_sources_deb822()),
+            SourcesFormat::Deb822 => (paths.apt_sources_deb822(), paths.apt_config()),
+        }
+    }
 
-        Ok(())
+    /// Unified diff of status.json and APT sources against what `apply_config` would write.
+    pub fn diff(&self, branches: &Branches, paths: &Paths) -> Result<String> {
+        let mut res = String::new();
+
+        let old_status = read_or_empty(&self.status_file)?;
+        // status.json is written in a single line, pretty print both sides for a readable diff
+        let old_status = serde_json::from_str::<MirrorStatus>(&old_status)
+            .and_then(|v| serde_json::to_string_pretty(&v))
+            .map(|s| s + "\n")
+            .unwrap_or(old_status);
+        let new_status = serde_json::to_string_pretty(&self.status)? + "\n";
+        res.push_str(&unified_diff(&self.status_file, &old_status, &new_status));
+
+        let (apt_path, legacy_path) = self.sources_paths(paths);
+        let old_sources = read_or_empty(&apt_path)?;
+        let new_sources = self.try_to_string(branches, paths)?;
+        res.push_str(&unified_diff(&apt_path, &old_sources, &new_sources));
+
+        if legacy_path.exists() {
+            let legacy = read_or_empty(&legacy_path)?;
+            res.push_str(&unified_diff(&legacy_path, &legacy, ""));
+        }
+
+        Ok(res)
+    }
+
+    /// Save the current status file and APT sources, so that they can be restored if applying fails.
+    pub fn snapshot(&self, paths: &Paths) -> Result<Snapshot> {
+        let (apt_path, legacy_path) = self.sources_paths(paths);
+        Snapshot::take(&[&self.status_file, &apt_path, &legacy_path])
+    }
+
+    /// Write the status file and APT sources, together: either both are replaced or neither is.
+    pub fn apply_config(&self, branches: &Branches, paths: &Paths) -> Result<()> {
+        let res = self.try_to_string(branches, paths)?;
+        let (apt_path, legacy_path) = self.sources_paths(paths);
+
+        let mut tx = Transaction::new(paths.commit_journal());
+        tx.write(&apt_path, res.as_bytes())
+            .context("Can not write apt config")?;
+        tx.write(&self.status_file, &serde_json::to_vec(&self.status)?)?;
+
+        // Switching formats: do not leave the same mirrors configured twice
+        if legacy_path.exists() {
+            info!(
+                "{}",
+                fl!(
+                    "remove-legacy-sources",
+                    path = legacy_path.display().to_string()
+                )
+            );
+            tx.remove(&legacy_path);
+        }
+
+        tx.commit()
     }
 
     pub fn list_enabled_mirrors(&self) -> Vec<&str> {
         self.status.list_enabled_mirrors()
     }
 }
+
+/// Look up a mirror to enable by name or alias, refusing retired ones.
+fn resolve_enableable<'a>(
+    mirrors: &'a Mirrors,
+    name: &'a str,
+) -> Result<(&'a str, &'a MirrorInfo)> {
+    let Some(resolved) = mirrors.resolve(name) else {
+        bail!(fl!("mirror-not-found", mirror = name));
+    };
+
+    let info = &mirrors.0[resolved];
+
+    if info.retired {
+        bail!(fl!("mirror-retired", mirror = resolved));
+    }
+
+    if resolved != name {
+        info!("{}", fl!("mirror-resolved", name = name, mirror = resolved));
+    }
+
+    Ok((resolved, info))
+}
+
+/// Name of the mapping key starting at column 0 of `line`, which may be quoted.
+fn top_level_key(line: &str) -> Option<String> {
+    let line = line.trim_end();
+
+    match line.chars().next()? {
+        q @ ('"' | '\'') => {
+            // Index of the closing quote, which is one byte long. Quotes are escaped as `\"` in
+            // double quotes and as `''` in single quotes.
+            let mut chars = line.char_indices().skip(1).peekable();
+            let close = loop {
+                let (i, c) = chars.next()?;
+
+                if q == '"' && c == '\\' {
+                    chars.next();
+                } else if c == q {
+                    if q == '\'' && chars.next_if(|(_, c)| *c == q).is_some() {
+                        continue;
+                    }
+                    break i;
+                }
+            };
+
+            let (key, rest) = line.split_at(close + 1);
+
+            if !rest.trim_start().starts_with(':') {
+                return None;
+            }
+
+            // Unquote with the YAML parser, so escapes are handled the same way
+            serde_yaml::from_str(key).ok()
+        }
+        c if c.is_whitespace() || matches!(c, '#' | '-' | '{' | '[' | '?') => None,
+        _ => {
+            let key = match line.split_once(": ") {
+                Some((key, _)) => key,
+                None => line.strip_suffix(':')?,
+            };
+
+            Some(key.trim_end().to_string())
+        }
+    }
+}
+
+pub fn read_or_empty(path: &Path) -> Result<String> {
+    match fs::read_to_string(path) {
+        Ok(s) => Ok(s),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn unified_diff(path: &Path, old: &str, new: &str) -> String {
+    if old == new {
+        return String::new();
+    }
+
+    let path = path.display().to_string();
+
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .header(&path, &path)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_mirror_forms() {
+        let custom = CustomMirrors::parse(
+            "\
+nas: https://nas.example/aosc/
+lab:
+  url: https://lab.example/aosc/
+  desc: Lab mirror
+  location: Room 101
+  priority: -5
+  notes: Only reachable from the lab
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            custom.get("nas"),
+            Some(&CustomMirror {
+                url: "https://nas.example/aosc/".to_string(),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            custom.get("lab"),
+            Some(&CustomMirror {
+                url: "https://lab.example/aosc/".to_string(),
+                desc: Some("Lab mirror".to_string()),
+                location: Some("Room 101".to_string()),
+                priority: -5,
+                notes: Some("Only reachable from the lab".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn custom_mirror_rejects_bad_entries() {
+        assert!(CustomMirrors::parse("nas:\n  desc: No URL\n").is_err());
+        assert!(CustomMirrors::parse("nas:\n  url: https://nas.example/\n  prio: 1\n").is_err());
+        assert!(CustomMirrors::parse("nas: [https://nas.example/]\n").is_err());
+    }
+
+    #[test]
+    fn custom_mirrors_round_trip() {
+        let custom = CustomMirrors(indexmap! {
+            "nas".to_string() => CustomMirror {
+                url: "https://nas.example/aosc/".to_string(),
+                ..Default::default()
+            },
+            "lab".to_string() => CustomMirror {
+                url: "https://lab.example/aosc/".to_string(),
+                desc: Some("Lab mirror".to_string()),
+                priority: 10,
+                ..Default::default()
+            },
+        });
+
+        let s = custom.to_yaml().unwrap();
+        assert!(s.starts_with(CUSTOM_MIRRORS_HEADER));
+        // Only the URL is set, so it stays in the short form
+        assert!(s.contains("\nnas: https://nas.example/aosc/\n"));
+
+        let parsed = CustomMirrors::parse(&s).unwrap();
+        assert_eq!(parsed.0, custom.0);
+        assert_eq!(
+            parsed.0.keys().collect::<Vec<_>>(),
+            custom.0.keys().collect::<Vec<_>>()
+        );
+
+        assert_eq!(
+            CustomMirrors::default().to_yaml().unwrap(),
+            CUSTOM_MIRRORS_HEADER
+        );
+    }
+
+    #[test]
+    fn line_of_entries() {
+        let s = "\
+# nas: commented out
+nas: https://nas.example/
+\"my mirror\":
+  url: https://mine.example/
+'it''s': https://its.example/
+café:
+  url: https://cafe.example/
+é
+";
+
+        assert_eq!(CustomMirrors::line_of(s, "nas"), Some(2));
+        assert_eq!(CustomMirrors::line_of(s, "my mirror"), Some(3));
+        assert_eq!(CustomMirrors::line_of(s, "it's"), Some(5));
+        assert_eq!(CustomMirrors::line_of(s, "café"), Some(6));
+        assert_eq!(CustomMirrors::line_of(s, "url"), None);
+        assert_eq!(CustomMirrors::line_of(s, "é"), None);
+    }
+
+    fn custom(entries: &[(&str, &str)]) -> CustomMirrors {
+        CustomMirrors(
+            entries
+                .iter()
+                .map(|(name, url)| {
+                    let mirror = CustomMirror {
+                        url: url.to_string(),
+                        ..Default::default()
+                    };
+                    (name.to_string(), mirror)
+                })
+                .collect(),
+        )
+    }
+
+    fn mirrors_with(custom: &CustomMirrors) -> Mirrors {
+        let mut mirrors: Mirrors = serde_yaml::from_str(
+            "\
+origin:
+  url: https://repo.aosc.io/
+  desc: AOSC main repo
+",
+        )
+        .unwrap();
+        mirrors.init_custom_mirrors(custom.clone()).unwrap();
+
+        mirrors
+    }
+
+    fn manager(enabled: &[(&str, &str)]) -> MirrorManager {
+        let status = MirrorStatus {
+            mirror: enabled
+                .iter()
+                .map(|(name, url)| (name.to_string(), url.to_string()))
+                .collect(),
+            ..Default::default()
+        };
+
+        MirrorManager::with_status(status, "/nonexistent/status.json")
+    }
+
+    #[test]
+    fn reconcile_custom_follows_renames() {
+        let old = custom(&[("nas", "https://nas.example/aosc/")]);
+        // Same mirror under a new name, the trailing slash does not matter
+        let new = custom(&[("home-nas", "https://nas.example/aosc")]);
+
+        let mut mm = manager(&[
+            ("nas", "https://nas.example/aosc/"),
+            ("origin", "https://repo.aosc.io/"),
+        ]);
+
+        assert!(mm
+            .reconcile_custom(&old.diff(&new), &mirrors_with(&new))
+            .unwrap());
+        assert_eq!(mm.list_enabled_mirrors(), vec!["home-nas", "origin"]);
+    }
+
+    #[test]
+    fn reconcile_custom_keeps_a_mirror_enabled() {
+        let old = custom(&[("nas", "https://nas.example/aosc/")]);
+        let new = custom(&[("lab", "https://lab.example/aosc/")]);
+
+        let mut mm = manager(&[("nas", "https://nas.example/aosc/")]);
+
+        assert!(mm
+            .reconcile_custom(&old.diff(&new), &mirrors_with(&new))
+            .is_err());
+        assert_eq!(mm.list_enabled_mirrors(), vec!["nas"]);
+
+        // Fine as long as another mirror stays enabled
+        let mut mm = manager(&[
+            ("nas", "https://nas.example/aosc/"),
+            ("origin", "https://repo.aosc.io/"),
+        ]);
+
+        assert!(mm
+            .reconcile_custom(&old.diff(&new), &mirrors_with(&new))
+            .unwrap());
+        assert_eq!(mm.list_enabled_mirrors(), vec!["origin"]);
+    }
+}
@@ -0,0 +1,101 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    APT_CONFIG, APT_SOURCES_DEB822, BRANCHES_PATH, COMMIT_JOURNAL, COMPONENTS_PATH, CUSTOM_MIRRORS,
+    CUSTOM_MIRRORS_D, HISTORY_FILE, MIRRORS_PATH, SPEEDTEST_RESULTS, STATUS_FILE,
+};
+
+/// Resolves mirrormgr data, status and output paths under a system root.
+#[derive(Debug, Clone)]
+pub struct Paths {
+    root: PathBuf,
+}
+
+impl Default for Paths {
+    fn default() -> Self {
+        Self::new("/")
+    }
+}
+
+impl Paths {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Join an absolute path (e.g. `/etc/apt/sources.list`) onto the root.
+    pub fn resolve<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        let path = path.as_ref();
+        self.root.join(path.strip_prefix("/").unwrap_or(path))
+    }
+
+    /// Directory of the status file and other mirrormgr state.
+    pub fn state_dir(&self) -> PathBuf {
+        self.status_file()
+            .parent()
+            .map(|p| p.to_path_buf())
+            .unwrap_or_else(|| self.root.clone())
+    }
+
+    pub fn status_file(&self) -> PathBuf {
+        self.resolve(STATUS_FILE)
+    }
+
+    pub fn mirrors(&self) -> PathBuf {
+        self.resolve(MIRRORS_PATH)
+    }
+
+    pub fn branches(&self) -> PathBuf {
+        self.resolve(BRANCHES_PATH)
+    }
+
+    pub fn components(&self) -> PathBuf {
+        self.resolve(COMPONENTS_PATH)
+    }
+
+    pub fn apt_config(&self) -> PathBuf {
+        self.resolve(APT_CONFIG)
+    }
+
+    pub fn apt_sources_deb822(&self) -> PathBuf {
+        self.resolve(APT_SOURCES_DEB822)
+    }
+
+    pub fn custom_mirrors(&self) -> PathBuf {
+        self.resolve(CUSTOM_MIRRORS)
+    }
+
+    /// Directory of custom mirror drop-ins (`*.yml`)
+    pub fn custom_mirrors_d(&self) -> PathBuf {
+        self.resolve(CUSTOM_MIRRORS_D)
+    }
+
+    pub fn speedtest_results(&self) -> PathBuf {
+        self.resolve(SPEEDTEST_RESULTS)
+    }
+
+    pub fn commit_journal(&self) -> PathBuf {
+        self.resolve(COMMIT_JOURNAL)
+    }
+
+    pub fn history(&self) -> PathBuf {
+        self.resolve(HISTORY_FILE)
+    }
+
+    pub fn os_release(&self) -> PathBuf {
+        let etc = self.resolve("/etc/os-release");
+        // /etc/os-release is usually an absolute symlink, which would point back to the host
+        if etc.symlink_metadata().is_ok_and(|m| m.is_file()) {
+            etc
+        } else {
+            self.resolve("/usr/lib/os-release")
+        }
+    }
+
+    pub fn apt_lists(&self) -> PathBuf {
+        self.resolve("/var/lib/apt/lists")
+    }
+}
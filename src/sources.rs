@@ -0,0 +1,317 @@
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use eyre::{Context, Result};
+
+use crate::paths::Paths;
+
+/// A `deb` entry of APT sources, from either the one-line or the deb822 format.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceEntry {
+    /// Line number (from 1) of the entry in its file
+    pub line: usize,
+    pub uris: Vec<String>,
+    pub suites: Vec<String>,
+    pub components: Vec<String>,
+    pub signed_by: Option<String>,
+}
+
+/// Entries of an APT sources file.
+#[derive(Debug, Default)]
+pub struct Sources {
+    pub entries: Vec<SourceEntry>,
+    /// Line numbers of `deb` lines or stanzas which could not be parsed
+    pub malformed: Vec<usize>,
+}
+
+/// Parse `deb` lines of a one-line style sources.list. Comments and `deb-src` are skipped.
+pub fn parse_one_line(s: &str) -> Sources {
+    let mut res = Sources::default();
+
+    for (i, line) in s.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+
+        let Some(rest) = line.strip_prefix("deb") else {
+            continue;
+        };
+
+        // deb-src, or something else starting with deb
+        if !rest.starts_with([' ', '\t']) {
+            continue;
+        }
+
+        let mut rest = rest.trim_start();
+        let mut signed_by = None;
+
+        if let Some(options) = rest.strip_prefix('[') {
+            let Some((options, after)) = options.split_once(']') else {
+                res.malformed.push(i + 1);
+                continue;
+            };
+
+            for option in options.split_whitespace() {
+                if let Some(v) = option.strip_prefix("signed-by=") {
+                    signed_by = Some(v.to_string());
+                }
+            }
+
+            rest = after;
+        }
+
+        let mut fields = rest.split_whitespace();
+        let (Some(uri), Some(suite)) = (fields.next(), fields.next()) else {
+            res.malformed.push(i + 1);
+            continue;
+        };
+
+        res.entries.push(SourceEntry {
+            line: i + 1,
+            uris: vec![uri.to_string()],
+            suites: vec![suite.to_string()],
+            components: fields.map(|x| x.to_string()).collect(),
+            signed_by,
+        });
+    }
+
+    res
+}
+
+/// Parse the `deb` stanzas of a deb822 style .sources file. Stanzas with `Enabled: no` are
+/// skipped.
+pub fn parse_deb822(s: &str) -> Sources {
+    let mut res = Sources::default();
+
+    for (line, stanza) in stanzas(s) {
+        let mut entry = SourceEntry {
+            line,
+            ..Default::default()
+        };
+        let mut is_deb = false;
+        let mut enabled = true;
+
+        for (k, v) in stanza {
+            let values = || v.split_whitespace().map(|x| x.to_string()).collect();
+
+            match k.to_ascii_lowercase().as_str() {
+                "types" => is_deb = v.split_whitespace().any(|t| t == "deb"),
+                "uris" => entry.uris = values(),
+                "suites" => entry.suites = values(),
+                "components" => entry.components = values(),
+                "signed-by" => entry.signed_by = Some(v.trim().to_string()),
+                "enabled" => enabled = v.trim() != "no",
+                _ => {}
+            }
+        }
+
+        if !is_deb || !enabled {
+            continue;
+        }
+
+        if entry.uris.is_empty() || entry.suites.is_empty() {
+            res.malformed.push(line);
+        } else {
+            res.entries.push(entry);
+        }
+    }
+
+    res
+}
+
+/// Split deb822 into stanzas of (field, value) with the line number they start at, joining
+/// continuation lines.
+fn stanzas(s: &str) -> Vec<(usize, Vec<(String, String)>)> {
+    let mut res = vec![];
+    let mut start = 0;
+    let mut stanza: Vec<(String, String)> = vec![];
+
+    for (i, line) in s.lines().enumerate() {
+        if line.starts_with('#') {
+            continue;
+        }
+
+        if line.trim().is_empty() {
+            if !stanza.is_empty() {
+                res.push((start, std::mem::take(&mut stanza)));
+            }
+            continue;
+        }
+
+        if stanza.is_empty() {
+            start = i + 1;
+        }
+
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, v)) = stanza.last_mut() {
+                v.push('\n');
+                v.push_str(line.trim());
+            }
+            continue;
+        }
+
+        if let Some((k, v)) = line.split_once(':') {
+            stanza.push((k.trim().to_string(), v.trim().to_string()));
+        }
+    }
+
+    if !stanza.is_empty() {
+        res.push((start, stanza));
+    }
+
+    res
+}
+
+/// Mirror URL of a repository URI, i.e. without the trailing `debs` or `debs-retro` directory.
+pub fn mirror_url(uri: &str) -> String {
+    let uri = uri.trim_end_matches('/');
+
+    let base = uri
+        .strip_suffix("/debs-retro")
+        .or_else(|| uri.strip_suffix("/debs"))
+        .unwrap_or(uri);
+
+    format!("{base}/")
+}
+
+/// Read sources.list and all .list and .sources files in sources.list.d, in the order APT
+/// reads them.
+pub fn read_all(paths: &Paths) -> Result<Vec<(PathBuf, Sources)>> {
+    let mut res = vec![];
+
+    let sources_list = paths.apt_config();
+    if let Some(s) = read_if_exists(&sources_list)? {
+        res.push((sources_list, parse_one_line(&s)));
+    }
+
+    let dir = paths.resolve("/etc/apt/sources.list.d");
+    let mut files = match fs::read_dir(&dir) {
+        Ok(d) => d
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_file())
+            .collect::<Vec<_>>(),
+        Err(e) if e.kind() == ErrorKind::NotFound => vec![],
+        Err(e) => return Err(e).context(format!("Can not read {}", dir.display())),
+    };
+    files.sort();
+
+    for path in files {
+        let parse = match path.extension().and_then(|x| x.to_str()) {
+            Some("list") => parse_one_line,
+            Some("sources") => parse_deb822,
+            _ => continue,
+        };
+
+        if let Some(s) = read_if_exists(&path)? {
+            res.push((path, parse(&s)));
+        }
+    }
+
+    Ok(res)
+}
+
+fn read_if_exists(path: &Path) -> Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(s) => Ok(Some(s)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).context(format!("Can not read {}", path.display())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_line() {
+        let s = "\
+# deb https://commented.example/debs stable main
+deb https://repo.aosc.io/debs stable main contrib
+deb-src https://repo.aosc.io/debs stable main
+deb [arch=amd64 signed-by=/etc/apt/keys/aosc.gpg] https://mirror.example/debs/ stable main # trailing
+deb https://broken.example/debs
+deb [signed-by=/etc/apt/keys/aosc.gpg https://broken.example/debs stable main
+";
+        let res = parse_one_line(s);
+
+        assert_eq!(
+            res.entries,
+            vec![
+                SourceEntry {
+                    line: 2,
+                    uris: vec!["https://repo.aosc.io/debs".to_string()],
+                    suites: vec!["stable".to_string()],
+                    components: vec!["main".to_string(), "contrib".to_string()],
+                    signed_by: None,
+                },
+                SourceEntry {
+                    line: 4,
+                    uris: vec!["https://mirror.example/debs/".to_string()],
+                    suites: vec!["stable".to_string()],
+                    components: vec!["main".to_string()],
+                    signed_by: Some("/etc/apt/keys/aosc.gpg".to_string()),
+                },
+            ]
+        );
+        assert_eq!(res.malformed, vec![5, 6]);
+    }
+
+    #[test]
+    fn deb822() {
+        let s = "\
+# AOSC OS
+Types: deb deb-src
+URIs: https://repo.aosc.io/debs
+  https://mirror.example/debs
+Suites: stable
+Components: main
+Signed-By: /etc/apt/keys/aosc.gpg
+
+Types: deb
+URIs: https://disabled.example/debs
+Suites: stable
+Enabled: no
+
+Types: deb-src
+URIs: https://repo.aosc.io/debs
+Suites: stable
+
+types: deb
+uris: https://broken.example/debs
+";
+        let res = parse_deb822(s);
+
+        assert_eq!(
+            res.entries,
+            vec![SourceEntry {
+                line: 2,
+                uris: vec![
+                    "https://repo.aosc.io/debs".to_string(),
+                    "https://mirror.example/debs".to_string()
+                ],
+                suites: vec!["stable".to_string()],
+                components: vec!["main".to_string()],
+                signed_by: Some("/etc/apt/keys/aosc.gpg".to_string()),
+            }]
+        );
+        assert_eq!(res.malformed, vec![18]);
+    }
+
+    #[test]
+    fn mirror_url_strips_debs() {
+        assert_eq!(
+            mirror_url("https://repo.aosc.io/debs"),
+            "https://repo.aosc.io/"
+        );
+        assert_eq!(
+            mirror_url("https://mirror.example/anthon/debs-retro/"),
+            "https://mirror.example/anthon/"
+        );
+        assert_eq!(
+            mirror_url("https://mirror.example/aosc"),
+            "https://mirror.example/aosc/"
+        );
+    }
+}
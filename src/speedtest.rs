@@ -0,0 +1,100 @@
+use std::{collections::HashMap, fmt::Display, fs, path::Path, time::Duration};
+
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    fl,
+    transaction::write_atomic,
+    utils::{age, format_age, now_secs},
+};
+
+/// Results older than this are shown as stale.
+pub const STALE_AFTER: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// The last speedtest result of a mirror.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct SpeedRecord {
+    /// KiB/s, `None` if the mirror failed the test
+    speed: Option<f32>,
+    timestamp: u64,
+}
+
+impl SpeedRecord {
+    pub fn new(speed: Option<f32>) -> Self {
+        Self {
+            speed,
+            timestamp: now_secs(),
+        }
+    }
+
+    pub fn speed(&self) -> Option<f32> {
+        self.speed
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn age(&self) -> Duration {
+        age(self.timestamp)
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.age() > STALE_AFTER
+    }
+}
+
+impl Display for SpeedRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let age = format_age(self.age());
+        let s = match self.speed {
+            Some(speed) => fl!("speed-record", speed = format_speed(speed), age = age),
+            None => fl!("speed-record-failed", age = age),
+        };
+
+        f.write_str(&s)?;
+
+        if self.is_stale() {
+            write!(f, ", {}", fl!("speed-record-stale"))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Speedtest results of every tested mirror, stored as JSON.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct SpeedtestResults(HashMap<String, SpeedRecord>);
+
+impl SpeedtestResults {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let f = fs::read(path)?;
+        let s = serde_json::from_slice(&f)?;
+
+        Ok(s)
+    }
+
+    pub fn get(&self, mirror: &str) -> Option<&SpeedRecord> {
+        self.0.get(mirror)
+    }
+
+    pub fn insert(&mut self, mirror: String, record: SpeedRecord) {
+        self.0.insert(mirror, record);
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        write_atomic(path, &serde_json::to_vec(self)?)
+    }
+}
+
+pub fn format_speed(score: f32) -> String {
+    let mut score = score;
+    let mut unit = "KiB/s";
+    if score > 1000.0 {
+        score /= 1024.0;
+        unit = "MiB/s";
+    }
+
+    format!("{:.2}{}", score, unit)
+}
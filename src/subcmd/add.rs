@@ -1,39 +1,57 @@
 use eyre::Result;
-use tracing::info;
+
+use mirrormgr::mgr::{Branches, Comps, DistroConfig};
 
 use crate::{
-    args::NormalArgs,
-    fl,
-    mgr::{Branches, Comps, DistroConfig, MirrorManager},
-    utils::{create_status, distro_and_custom_mirrors, refresh, root},
-    APT_CONFIG, BRANCHES_PATH, COMPONENTS_PATH, STATUS_FILE,
+    args::Add,
+    context::Context,
+    subcmd::{
+        root,
+        speedtest::{self, SpeedtestOptions},
+    },
 };
 
-pub fn execute(args: NormalArgs) -> Result<()> {
+pub fn execute(args: Add, ctx: &Context) -> Result<()> {
     root()?;
-    let status = create_status(STATUS_FILE)?;
-    let mut mm = MirrorManager::new(status);
+    let _lock = ctx.lock()?;
+    let mut mm = ctx.load_status()?;
 
     if let Some(mirrors) = args.mirrors {
-        let mm_info = distro_and_custom_mirrors()?;
+        let mm_info = ctx.mirrors()?;
         mm.add_mirrors(
             &mm_info,
             &mirrors.iter().map(|x| x.as_str()).collect::<Vec<_>>(),
         )?;
     }
 
+    if let Some(count) = args.fastest {
+        let mm_info = ctx.mirrors()?;
+        let opts = SpeedtestOptions::fastest(args.jobs, args.timeout);
+        let fastest = speedtest::fastest(ctx, &mm_info, count, args.min_speed, &opts)?;
+        mm.add_mirrors(
+            &mm_info,
+            &fastest.iter().map(|x| x.as_str()).collect::<Vec<_>>(),
+        )?;
+
+        // Put the fastest mirrors first, in order of speed
+        let mut order = fastest.clone();
+        order.extend(
+            mm.list_enabled_mirrors()
+                .into_iter()
+                .filter(|x| !fastest.iter().any(|f| f == x))
+                .map(|x| x.to_string()),
+        );
+        mm.reorder_mirrors(order);
+    }
+
     if let Some(comps) = args.components {
-        let comps_info = Comps::from_path(COMPONENTS_PATH)?;
+        let comps_info = Comps::from_path(ctx.paths.components())?;
         mm.add_components(&comps_info, comps)?;
     }
 
-    let branches = Branches::from_path(BRANCHES_PATH)?;
-
-    info!("{}", fl!("write-sources"));
-    mm.apply_config(&branches, APT_CONFIG)?;
+    let branches = Branches::from_path(ctx.paths.branches())?;
 
-    info!("{}", fl!("run-refresh"));
-    refresh()?;
+    ctx.apply(&mm, &branches, true)?;
 
     Ok(())
 }
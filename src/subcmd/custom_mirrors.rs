@@ -1,135 +1,523 @@
-use eyre::{eyre, Result};
-use std::collections::HashMap;
+use dialoguer::{console::Term, theme::ColorfulTheme, Select};
+use eyre::{bail, eyre, Result};
+use serde::Serialize;
 use std::io::Write;
+use std::path::Path;
 use std::process::Command;
-use std::{env, fs, path::Path};
-use tracing::{info, error};
+use std::{env, fs};
+use tabled::{settings::Style, Table, Tabled};
+use tracing::{error, info, warn};
 
-use crate::mgr::{Branches, CustomMirrors, DistroConfig, MirrorManager};
-use crate::utils::{create_status, distro_and_custom_mirrors, refresh};
-use crate::{fl, APT_CONFIG, BRANCHES_PATH, STATUS_FILE};
-use crate::{utils::root, CUSTOM_MIRRORS};
+use crate::args::{Custom, CustomAction, CustomMirrorDetails};
+use crate::context::{print_diff, Context};
+use crate::subcmd::{display_enabled, root};
+use mirrormgr::fl;
+use mirrormgr::mgr::{
+    read_or_empty, unified_diff, Branches, CustomMirror, CustomMirrorProblem, CustomMirrors,
+    CustomMirrorsDiff, DistroConfig, MergedCustomMirrors, MirrorManager, Mirrors,
+    CUSTOM_MIRRORS_HEADER,
+};
+use mirrormgr::transaction::write_atomic;
 
-pub fn execute() -> Result<()> {
-    root()?;
+#[derive(Serialize, Tabled)]
+struct ListEntry {
+    #[tabled(rename = "Name")]
+    name: String,
+    #[tabled(rename = "URL")]
+    url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[tabled(rename = "Description", display_with = "display_option")]
+    desc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[tabled(rename = "Location", display_with = "display_option")]
+    location: Option<String>,
+    #[tabled(rename = "Priority")]
+    priority: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[tabled(skip)]
+    notes: Option<String>,
+    #[tabled(rename = "File")]
+    file: String,
+    #[tabled(rename = "Enabled", display_with = "display_enabled")]
+    enabled: bool,
+}
 
-    let p = Path::new(CUSTOM_MIRRORS);
+fn display_option(s: &Option<String>) -> String {
+    s.clone().unwrap_or_default()
+}
 
-    if let Some(parent) = p.parent() {
-        if !p.is_dir() {
-            fs::create_dir_all(parent)?;
+pub fn execute(args: Custom, ctx: &Context) -> Result<()> {
+    match args.action {
+        Some(CustomAction::List { json }) => list(json, ctx),
+        Some(CustomAction::Add { name, url, details }) => add(name, url, details, ctx),
+        Some(CustomAction::Remove { name }) => remove(name, ctx),
+        Some(CustomAction::Edit {
+            name: Some(name),
+            url,
+            details,
+        }) => edit(name, url, details, ctx),
+        Some(CustomAction::Edit {
+            name: None,
+            url,
+            details,
+        }) => {
+            if url.is_some() || !details.is_empty() {
+                bail!(fl!("custom-edit-needs-name"));
+            }
+            edit_in_editor(ctx)
         }
+        None => edit_in_editor(ctx),
     }
+}
 
-    let mut f = fs::OpenOptions::new()
-        .create(true)
-        .write(true)
-        .read(true)
-        .open(p)?;
+fn list(json: bool, ctx: &Context) -> Result<()> {
+    let distro = Mirrors::from_path(ctx.paths.mirrors())?;
+    let merged = CustomMirrors::load_all(&ctx.paths, &distro);
+    let mm = MirrorManager::new(ctx.paths.status_file())?;
 
-    let len = f.metadata()?.len();
+    for problem in &merged.problems {
+        warn!("{problem}");
+    }
 
-    let custom_map = if len == 0 {
-        f.write_all(b"# AOSC OS mirrormgr custom mirror config file\n")?;
-        f.write_all(b"# Usage: custom_mirror_name: URL\n")?;
-        f.write_all(b"# Like: MY_NAS: https://localhost/aosc\n")?;
-        f.write_all(b"# After, you can run `mirrormgr set --mirror MY_NAS' to use it.\n\n")?;
+    let mut entries = merged
+        .mirrors
+        .0
+        .into_iter()
+        .map(|(name, mirror)| ListEntry {
+            enabled: mm.status().mirrors().contains_key(&name),
+            file: merged.files[&name].display().to_string(),
+            name,
+            url: mirror.url,
+            desc: mirror.desc,
+            location: mirror.location,
+            priority: mirror.priority,
+            notes: mirror.notes,
+        })
+        .collect::<Vec<_>>();
 
-        CustomMirrors(HashMap::new())
-    } else {
-        match DistroConfig::from_file(&f) {
-            Ok(config) => config,
-            Err(err) => {
-                error!("{}", fl!("custom-parse-failed", custom_path = CUSTOM_MIRRORS));
-                info!("{}", fl!("fix-custom-howto"));
-                return Err(eyre!(err));
-            }
-        }
+    // Stable, so that mirrors of the same priority stay in file order
+    entries.sort_by_key(|e| std::cmp::Reverse(e.priority));
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        info!("{}", fl!("custom-list-empty"));
+        return Ok(());
+    }
+
+    let mut t = Table::new(entries);
+    t.with(Style::psql());
+    println!("{t}");
+
+    Ok(())
+}
+
+fn add(name: String, url: String, details: CustomMirrorDetails, ctx: &Context) -> Result<()> {
+    root()?;
+    let _lock = ctx.lock()?;
+
+    let path = ctx.paths.custom_mirrors();
+    let distro = Mirrors::from_path(ctx.paths.mirrors())?;
+    let merged = CustomMirrors::load_all(&ctx.paths, &distro);
+
+    CustomMirrors::validate_name(&name, &distro)?;
+    CustomMirrors::validate_url(&url)?;
+
+    // Also fails on a broken main file, instead of appending to it
+    let old_s = read_or_empty(&path)?;
+    let old = CustomMirrors::parse(&old_s)?;
+
+    if merged.mirrors.has(&name) {
+        bail!(fl!("custom-exists", name = name.as_str()));
+    }
+
+    let mut mirror = CustomMirror {
+        url,
+        ..Default::default()
+    };
+    update(&mut mirror, details);
+
+    let mut custom = old.clone();
+    custom.0.insert(name.clone(), mirror.clone());
+
+    save(
+        ctx,
+        &CustomMirrors::appended(&old_s, &[(name, mirror)])?,
+        None,
+    )?;
+    report(ctx, &old.diff(&custom));
+
+    Ok(())
+}
+
+fn remove(name: String, ctx: &Context) -> Result<()> {
+    root()?;
+    let _lock = ctx.lock()?;
+
+    let old = CustomMirrors::load(ctx.paths.custom_mirrors())?;
+    let distro = Mirrors::from_path(ctx.paths.mirrors())?;
+    in_main_file(&name, &CustomMirrors::load_all(&ctx.paths, &distro), ctx)?;
+
+    let mut custom = old.clone();
+
+    if custom.0.shift_remove(&name).is_none() {
+        bail!(fl!("custom-not-found", name = name));
+    }
+
+    let contents = render(ctx, &name, &custom)?;
+    let mm = reconcile(ctx, &old, &custom)?;
+    save(ctx, &contents, mm.as_ref())?;
+    report(ctx, &old.diff(&custom));
+
+    Ok(())
+}
+
+fn edit(
+    name: String,
+    url: Option<String>,
+    details: CustomMirrorDetails,
+    ctx: &Context,
+) -> Result<()> {
+    root()?;
+    let _lock = ctx.lock()?;
+
+    if url.is_none() && details.is_empty() {
+        bail!(fl!("custom-edit-nothing"));
+    }
+
+    let old = CustomMirrors::load(ctx.paths.custom_mirrors())?;
+    let distro = Mirrors::from_path(ctx.paths.mirrors())?;
+    in_main_file(&name, &CustomMirrors::load_all(&ctx.paths, &distro), ctx)?;
+
+    let mut custom = old.clone();
+
+    let Some(entry) = custom.0.get_mut(&name) else {
+        bail!(fl!("custom-not-found", name = name));
     };
 
-    drop(f);
+    if let Some(url) = url {
+        CustomMirrors::validate_url(&url)?;
+        entry.url = url;
+    }
+
+    update(entry, details);
+
+    let contents = render(ctx, &name, &custom)?;
+    let mm = reconcile(ctx, &old, &custom)?;
+    save(ctx, &contents, mm.as_ref())?;
+    report(ctx, &old.diff(&custom));
+
+    Ok(())
+}
+
+/// The main custom mirror file with the entry `name` changed or removed as in `custom`. Only that
+/// entry is rewritten, unless it cannot be found, then the whole file is.
+fn render(ctx: &Context, name: &str, custom: &CustomMirrors) -> Result<String> {
+    let path = ctx.paths.custom_mirrors();
+    let s = fs::read_to_string(&path)?;
+
+    match CustomMirrors::replace_entry(&s, name, custom.get(name))? {
+        // Make sure nothing else was changed by mistake
+        Some(new) if CustomMirrors::parse(&new).is_ok_and(|c| c.0 == custom.0) => Ok(new),
+        _ => {
+            warn!(
+                "{}",
+                fl!(
+                    "custom-rewrite",
+                    name = name,
+                    path = path.display().to_string()
+                )
+            );
+            custom.to_yaml()
+        }
+    }
+}
+
+/// Bring the enabled mirrors in line with the custom mirrors going from `old` to `new`, returning
+/// the status to apply if it changed.
+fn reconcile(
+    ctx: &Context,
+    old: &CustomMirrors,
+    new: &CustomMirrors,
+) -> Result<Option<MirrorManager>> {
+    let diff = old.diff(new);
+
+    if diff.is_empty() {
+        return Ok(None);
+    }
+
+    let mut mm = ctx.load_status()?;
+    let mut mirrors = Mirrors::from_path(ctx.paths.mirrors())?;
+    let custom = CustomMirrors::load_all_with(&ctx.paths, &mirrors, Some(new.clone()));
+    mirrors.init_custom_mirrors(custom.mirrors)?;
+
+    Ok(mm.reconcile_custom(&diff, &mirrors)?.then_some(mm))
+}
 
+/// Open `path` in $EDITOR, or nano if it is not set.
+fn run_editor(path: &Path) -> Result<()> {
     let editor = env::var("EDITOR").unwrap_or("nano".to_string());
-    Command::new(editor).arg(p).spawn()?.wait()?;
-
-    let custom_map2: CustomMirrors = match DistroConfig::from_path(p) {
-        Ok(config) => config,
-        Err(err) => {
-            error!("{}", fl!("custom-parse-failed", custom_path = CUSTOM_MIRRORS));
-            info!("{}", fl!("fix-custom-howto"));
-            return Err(eyre!(err));
+    let mut args = editor.split_whitespace();
+    let program = args.next().unwrap_or("nano");
+
+    Command::new(program).args(args).arg(path).spawn()?.wait()?;
+
+    Ok(())
+}
+
+/// Parse an edited custom mirror file and check the names and URLs added or changed since
+/// `old`, returning every problem found with the line it is on. Entries left as they were are
+/// accepted as they load, even if they would not be valid as new ones.
+fn check(
+    ctx: &Context,
+    s: &str,
+    old: &CustomMirrors,
+    distro: &Mirrors,
+) -> std::result::Result<CustomMirrors, Vec<String>> {
+    // serde_yaml errors already tell the line and column
+    let custom = CustomMirrors::parse(s).map_err(|e| vec![e.to_string()])?;
+
+    let merged = CustomMirrors::load_all_with(&ctx.paths, distro, Some(custom.clone()));
+    let main = ctx.paths.custom_mirrors();
+
+    let errors = custom
+        .0
+        .iter()
+        .flat_map(|(name, mirror)| {
+            let mut res = vec![];
+
+            if !old.has(name) {
+                res.push(CustomMirrors::validate_name(name, distro));
+
+                // Drop-ins come after the main file, so theirs is the entry left out
+                for problem in &merged.problems {
+                    if let CustomMirrorProblem::Duplicate {
+                        name: n,
+                        path,
+                        first,
+                    } = problem
+                    {
+                        if n == name && *first == main {
+                            res.push(Err(eyre!(fl!(
+                                "custom-exists-in",
+                                name = name.as_str(),
+                                path = path.display().to_string()
+                            ))));
+                        }
+                    }
+                }
+            }
+
+            if old.get(name) != Some(mirror) {
+                res.push(CustomMirrors::validate_url(&mirror.url));
+            }
+
+            res.into_iter().filter_map(|r| r.err()).map(|e| {
+                fl!(
+                    "custom-error-at-line",
+                    line = CustomMirrors::line_of(s, name).unwrap_or_default(),
+                    error = e.to_string()
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+
+    if errors.is_empty() {
+        Ok(custom)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Drop-ins are owned by whoever installed them, so only mirrors of the main file are changed.
+fn in_main_file(name: &str, merged: &MergedCustomMirrors, ctx: &Context) -> Result<()> {
+    match merged.files.get(name) {
+        Some(path) if *path != ctx.paths.custom_mirrors() => bail!(fl!(
+            "custom-in-dropin",
+            name = name,
+            path = path.display().to_string()
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Set the fields given on the command line, an empty value clears them.
+fn update(mirror: &mut CustomMirror, details: CustomMirrorDetails) {
+    let set = |field: &mut Option<String>, value: Option<String>| {
+        if let Some(value) = value {
+            *field = Some(value).filter(|x| !x.is_empty());
         }
     };
 
-    let mut edited_map = HashMap::new();
+    set(&mut mirror.desc, details.desc);
+    set(&mut mirror.location, details.location);
+    set(&mut mirror.notes, details.notes);
+
+    if let Some(priority) = details.priority {
+        mirror.priority = priority;
+    }
+}
+
+/// Write the custom mirror file and apply `mm` if enabled mirrors changed along with it. The
+/// file is put back if the configuration could not be applied. A dry run only shows the changes.
+pub(crate) fn save(ctx: &Context, contents: &str, mm: Option<&MirrorManager>) -> Result<()> {
+    let path = ctx.paths.custom_mirrors();
+    let branches = Branches::from_path(ctx.paths.branches())?;
+    let existed = path.exists();
+    let old = read_or_empty(&path)?;
 
-    let mut is_edited = false;
+    if ctx.dry_run {
+        print_diff(&unified_diff(&path, &old, contents));
 
-    for (k, v) in custom_map.0 {
-        if let Some(v2) = custom_map2.0.get(&k) {
-            if &v != v2 {
-                edited_map.insert(k, v);
-                is_edited = true;
+        return match mm {
+            Some(mm) => ctx.apply(mm, &branches, true),
+            None => {
+                info!("{}", fl!("dry-run-nothing-written"));
+                Ok(())
             }
-        } else {
-            edited_map.insert(k, "".to_string());
-            is_edited = true;
-        }
+        };
     }
 
-    if is_edited {
-        let status = create_status(STATUS_FILE)?;
-        let mut mm = MirrorManager::new(status);
-        let branches = Branches::from_path(BRANCHES_PATH)?;
+    write_atomic(&path, contents.as_bytes())?;
 
-        let mut removed_mirrors = vec![];
-        let mut edited_mirrors = vec![];
+    let Some(mm) = mm else {
+        return Ok(());
+    };
 
-        for (k, v) in edited_map {
-            if v == "" {
-                removed_mirrors.push(k);
+    if let Err(e) = ctx.apply(mm, &branches, true) {
+        // Rolled back, or failed before writing anything
+        if MirrorManager::new(ctx.paths.status_file())?.status() != mm.status() {
+            if existed {
+                write_atomic(&path, old.as_bytes())?;
             } else {
-                edited_mirrors.push(k);
+                fs::remove_file(&path)?;
             }
         }
+        return Err(e);
+    }
 
-        let enabled_mirrors = mm.list_enabled_mirrors();
+    Ok(())
+}
 
-        let mut i = 0;
-        while i < removed_mirrors.len() {
-            if !enabled_mirrors.contains(&removed_mirrors[i].as_str()) {
-                removed_mirrors.remove(i);
-            } else {
-                i += 1;
-            }
+/// Tell which custom mirrors were added, removed and changed, or would be in a dry run.
+fn report(ctx: &Context, diff: &CustomMirrorsDiff) {
+    for name in &diff.added {
+        let name = name.as_str();
+        if ctx.dry_run {
+            info!("{}", fl!("custom-would-add", name = name));
+        } else {
+            info!("{}", fl!("custom-added", name = name));
+        }
+    }
+    for name in &diff.removed {
+        let name = name.as_str();
+        if ctx.dry_run {
+            info!("{}", fl!("custom-would-remove", name = name));
+        } else {
+            info!("{}", fl!("custom-removed", name = name));
+        }
+    }
+    for name in &diff.changed {
+        let name = name.as_str();
+        if ctx.dry_run {
+            info!("{}", fl!("custom-would-edit", name = name));
+        } else {
+            info!("{}", fl!("custom-edited", name = name));
         }
+    }
+    if diff.is_empty() {
+        info!("{}", fl!("custom-unchanged"));
+    }
+}
 
-        let mut i = 0;
-        while i < edited_mirrors.len() {
-            if !enabled_mirrors.contains(&removed_mirrors[i].as_str()) {
-                edited_mirrors.remove(i);
-            } else {
-                i += 1;
-            }
+fn edit_in_editor(ctx: &Context) -> Result<()> {
+    root()?;
+    let _lock = ctx.lock()?;
+
+    let p = ctx.paths.custom_mirrors();
+    let old = read_or_empty(&p)?;
+
+    // A broken file is fixed in the editor like any other mistake
+    let custom_map = CustomMirrors::parse(&old).unwrap_or_default();
+    let distro = Mirrors::from_path(ctx.paths.mirrors())?;
+
+    // Like visudo, edit a copy and only replace the file once it is valid. A dry run leaves the
+    // target tree alone and edits it in the temporary directory.
+    let mut tmp = tempfile::Builder::new();
+    tmp.prefix(".custom_mirror.").suffix(".yml");
+
+    let mut tmp = if ctx.dry_run {
+        tmp.tempfile()?
+    } else {
+        let dir = p
+            .parent()
+            .expect("Custom mirror file has a parent directory");
+        fs::create_dir_all(dir)?;
+        tmp.tempfile_in(dir)?
+    };
+
+    tmp.write_all(if old.trim().is_empty() {
+        CUSTOM_MIRRORS_HEADER.as_bytes()
+    } else {
+        old.as_bytes()
+    })?;
+    tmp.flush()?;
+
+    let (custom_map2, new, mm) = loop {
+        run_editor(tmp.path())?;
+        let new = fs::read_to_string(tmp.path())?;
+
+        let res = check(ctx, &new, &custom_map, &distro).and_then(|custom| {
+            reconcile(ctx, &custom_map, &custom)
+                .map(|mm| (custom, mm))
+                .map_err(|e| vec![e.to_string()])
+        });
+
+        let errors = match res {
+            Ok((custom, mm)) => break (custom, new, mm),
+            Err(errors) => errors,
+        };
+
+        for e in &errors {
+            error!("{e}");
         }
 
-        mm.remove_mirrors(&removed_mirrors)?;
-        mm.remove_mirrors(&edited_mirrors)?;
-        let mm_info = distro_and_custom_mirrors()?;
-        mm.add_mirrors(
-            &mm_info,
-            &edited_mirrors
-                .iter()
-                .map(|x| x.as_str())
-                .collect::<Vec<_>>(),
-        )?;
+        let choice = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(fl!("custom-fix-prompt"))
+            .items(&[
+                fl!("custom-reedit"),
+                fl!("custom-discard"),
+                fl!("custom-keep-broken"),
+            ])
+            .default(0)
+            .interact_on_opt(&Term::stderr())?;
 
-        info!("{}", fl!("write-sources"));
-        mm.apply_config(&branches, APT_CONFIG)?;
+        match choice {
+            Some(0) => continue,
+            Some(2) => {
+                save(ctx, &new, None)?;
+                if !ctx.dry_run {
+                    info!("{}", fl!("fix-custom-howto"));
+                }
+                return Ok(());
+            }
+            _ => {
+                info!("{}", fl!("custom-discarded"));
+                return Ok(());
+            }
+        }
+    };
 
-        info!("{}", fl!("run-refresh"));
-        refresh()?;
+    if new == old {
+        info!("{}", fl!("custom-unchanged"));
+        return Ok(());
     }
 
+    save(ctx, &new, mm.as_ref())?;
+    report(ctx, &custom_map.diff(&custom_map2));
+
     Ok(())
 }
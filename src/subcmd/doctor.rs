@@ -0,0 +1,198 @@
+use std::io::{stderr, stdin, IsTerminal};
+
+use dialoguer::{console::Term, theme::ColorfulTheme, Confirm, Select};
+use eyre::{bail, Result};
+use mirrormgr::{
+    fl,
+    mgr::{Branches, Comps, DistroConfig, MirrorManager, MirrorStatus, Mirrors},
+    verify::{verify, Finding},
+};
+use tracing::{info, warn};
+
+use crate::{
+    args::Doctor,
+    context::{print_diff, Context},
+    subcmd::root,
+};
+
+pub fn execute(args: Doctor, ctx: &Context) -> Result<()> {
+    root()?;
+    let _lock = ctx.lock()?;
+
+    if !args.yes && (!stdin().is_terminal() || !stderr().is_terminal()) {
+        bail!(fl!("doctor-needs-terminal"));
+    }
+
+    // Not migrated yet, so that replaced mirrors are reported and fixed like other findings
+    let mut mm = ctx.read_status()?;
+    let mirrors = ctx.mirrors()?;
+    let branches = Branches::from_path(ctx.paths.branches())?;
+    let comps = Comps::from_path(ctx.paths.components())?;
+
+    let mut findings = verify(&mm, &mirrors, &branches, &comps, &ctx.paths)?;
+
+    if findings.is_empty() {
+        info!("{}", fl!("verify-ok"));
+        return Ok(());
+    }
+
+    let mut changed = false;
+
+    // Importing the sources replaces the whole status, so decide on it before anything else
+    if let Some(drift @ Finding::SourcesDrift { diff, .. }) = findings
+        .iter()
+        .find(|f| matches!(f, Finding::SourcesDrift { .. }))
+    {
+        warn!("{drift}");
+        print_diff(diff);
+
+        match fix_sources(args.yes)? {
+            SourcesFix::Regenerate => changed = true,
+            SourcesFix::Import => {
+                let import = MirrorStatus::from_sources(&ctx.paths, &mirrors, &branches, &comps)?;
+
+                for reason in &import.unmapped {
+                    warn!("{reason}");
+                }
+
+                for url in &import.unknown_mirrors {
+                    warn!("{}", fl!("unknown-source-uri", uri = url.as_str()));
+                }
+
+                if import.status.mirrors().is_empty() {
+                    bail!(fl!("no-known-mirror-in-sources"));
+                }
+
+                mm = MirrorManager::with_status(import.status, ctx.paths.status_file());
+                findings = verify(&mm, &mirrors, &branches, &comps, &ctx.paths)?;
+                changed = true;
+            }
+            SourcesFix::Skip => {}
+        }
+    }
+
+    for finding in &findings {
+        match finding {
+            Finding::SourcesDrift { .. } => {}
+            Finding::LegacySources { .. } => {
+                warn!("{finding}");
+                // Applying removes it
+                if confirm(&fl!("doctor-fix-legacy"), args.yes)? {
+                    changed = true;
+                }
+            }
+            Finding::MirrorNotFound { mirror } => {
+                warn!("{finding}");
+                if confirm(
+                    &fl!("doctor-fix-mirror", mirror = mirror.as_str()),
+                    args.yes,
+                )? {
+                    remove_mirror(&mut mm, mirror, &mirrors)?;
+                    changed = true;
+                }
+            }
+            Finding::MirrorRetired { mirror } => {
+                warn!("{finding}");
+                if confirm(
+                    &fl!("doctor-fix-mirror", mirror = mirror.as_str()),
+                    args.yes,
+                )? {
+                    remove_mirror(&mut mm, mirror, &mirrors)?;
+                    changed = true;
+                }
+            }
+            Finding::MirrorReplaced { mirror, new } => {
+                warn!("{finding}");
+                if confirm(&fl!("doctor-fix-replaced", new = new.as_str()), args.yes)? {
+                    mm.replace_mirror(mirror, new, &mirrors)?;
+                    changed = true;
+                }
+            }
+            Finding::MirrorUrlChanged { mirror, .. } => {
+                warn!("{finding}");
+                if confirm(&fl!("doctor-fix-url"), args.yes)? {
+                    mm.replace_mirror(mirror, mirror, &mirrors)?;
+                    changed = true;
+                }
+            }
+            Finding::BranchNotFound { .. } => {
+                warn!("{finding}");
+                let branch = MirrorStatus::default().branch().to_string();
+                if confirm(
+                    &fl!("doctor-fix-branch", branch = branch.as_str()),
+                    args.yes,
+                )? {
+                    mm.set_branch(&branch, &branches)?;
+                    changed = true;
+                }
+            }
+            Finding::ComponentNotFound { comp } => {
+                warn!("{finding}");
+                if confirm(&fl!("doctor-fix-comp", comp = comp.as_str()), args.yes)? {
+                    mm.remove_components(vec![comp.clone()])?;
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    if changed {
+        info!("{}", fl!("doctor-fixed"));
+        ctx.apply(&mm, &branches, true)?;
+    }
+
+    Ok(())
+}
+
+enum SourcesFix {
+    Regenerate,
+    Import,
+    Skip,
+}
+
+fn fix_sources(yes: bool) -> Result<SourcesFix> {
+    if yes {
+        return Ok(SourcesFix::Regenerate);
+    }
+
+    let choice = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(fl!("doctor-fix-sources"))
+        .items(&[
+            fl!("doctor-regenerate"),
+            fl!("doctor-import"),
+            fl!("doctor-skip"),
+        ])
+        .default(0)
+        .interact_on(&Term::stderr())?;
+
+    Ok(match choice {
+        0 => SourcesFix::Regenerate,
+        1 => SourcesFix::Import,
+        _ => SourcesFix::Skip,
+    })
+}
+
+fn confirm(prompt: &str, yes: bool) -> Result<bool> {
+    if yes {
+        return Ok(true);
+    }
+
+    Ok(Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .default(true)
+        .interact_on(&Term::stderr())?)
+}
+
+/// Disable `mirror`, falling back to the default mirror if it was the only one.
+pub(crate) fn remove_mirror(mm: &mut MirrorManager, mirror: &str, mirrors: &Mirrors) -> Result<()> {
+    if mm.status().mirrors().len() > 1 {
+        return mm.remove_mirrors(&[mirror.to_string()]);
+    }
+
+    let default = MirrorStatus::default();
+    let (name, _) = default
+        .mirrors()
+        .first()
+        .expect("Default status has a mirror");
+    mm.set_mirror(name, mirrors)
+}
@@ -0,0 +1,89 @@
+use eyre::{bail, Result};
+use mirrormgr::{
+    fl,
+    history::{History, HistoryEntry},
+    mgr::{Branches, DistroConfig, MirrorManager},
+    utils::format_age,
+};
+use tabled::{settings::Style, Table, Tabled};
+use tracing::info;
+
+use crate::{
+    args::{self, HistoryAction},
+    context::Context,
+    subcmd::root,
+};
+
+#[derive(Tabled)]
+struct HistoryRow {
+    #[tabled(rename = "ID")]
+    id: u64,
+    #[tabled(rename = "Applied")]
+    applied: String,
+    #[tabled(rename = "Branch")]
+    branch: String,
+    #[tabled(rename = "Mirrors")]
+    mirrors: String,
+    #[tabled(rename = "Command")]
+    command: String,
+}
+
+impl From<&HistoryEntry> for HistoryRow {
+    fn from(value: &HistoryEntry) -> Self {
+        let status = value.status();
+
+        HistoryRow {
+            id: value.id(),
+            applied: fl!("history-age", age = format_age(value.age())),
+            branch: status.branch().to_string(),
+            mirrors: status
+                .mirrors()
+                .keys()
+                .map(|x| x.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            command: value.command().to_string(),
+        }
+    }
+}
+
+pub fn execute(args: args::History, ctx: &Context) -> Result<()> {
+    match args.action {
+        None => list(ctx),
+        Some(HistoryAction::Restore { id }) => restore(ctx, id),
+    }
+}
+
+fn list(ctx: &Context) -> Result<()> {
+    let history = History::from_path(ctx.paths.history()).unwrap_or_default();
+
+    // Newest first
+    let rows = history.entries().iter().rev().map(HistoryRow::from);
+
+    let mut t = Table::new(rows);
+    t.with(Style::psql());
+
+    println!("{t}");
+
+    Ok(())
+}
+
+fn restore(ctx: &Context, id: u64) -> Result<()> {
+    root()?;
+    let _lock = ctx.lock()?;
+
+    let history = History::from_path(ctx.paths.history()).unwrap_or_default();
+
+    let Some(entry) = history.get(id) else {
+        bail!(fl!("no-such-history-entry", id = id));
+    };
+
+    info!("{}", fl!("restore-to", id = id));
+
+    let mm = MirrorManager::with_status(entry.status().clone(), ctx.paths.status_file());
+    let branches = Branches::from_path(ctx.paths.branches())?;
+
+    ctx.apply(&mm, &branches, true)?;
+
+    Ok(())
+}
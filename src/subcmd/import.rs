@@ -0,0 +1,104 @@
+use eyre::{bail, Result};
+use tracing::{info, warn};
+use url::Url;
+
+use mirrormgr::{
+    fl,
+    mgr::{
+        read_or_empty, Branches, Comps, CustomMirror, CustomMirrors, DistroConfig, MirrorManager,
+        MirrorStatus, Mirrors,
+    },
+};
+
+use crate::{
+    context::Context,
+    subcmd::{custom_mirrors, root},
+};
+
+pub fn execute(ctx: &Context) -> Result<()> {
+    root()?;
+    let _lock = ctx.lock()?;
+
+    let mut mirrors = ctx.mirrors()?;
+    let branches = Branches::from_path(ctx.paths.branches())?;
+    let comps = Comps::from_path(ctx.paths.components())?;
+
+    let mut import = MirrorStatus::from_sources(&ctx.paths, &mirrors, &branches, &comps)?;
+    // Custom mirror file with the unknown mirrors added, saved together with the sources
+    let mut custom_file = None;
+
+    if !import.unknown_mirrors.is_empty() {
+        let mut custom: Vec<(String, CustomMirror)> = vec![];
+
+        for url in &import.unknown_mirrors {
+            let name = custom_mirror_name(url, &mirrors, &custom);
+            info!(
+                "{}",
+                fl!(
+                    "import-custom-mirror",
+                    url = url.as_str(),
+                    name = name.as_str()
+                )
+            );
+            custom.push((
+                name,
+                CustomMirror {
+                    url: url.clone(),
+                    ..Default::default()
+                },
+            ));
+        }
+
+        let s = read_or_empty(&ctx.paths.custom_mirrors())?;
+        custom_file = Some(CustomMirrors::appended(&s, &custom)?);
+
+        mirrors.init_custom_mirrors(CustomMirrors(custom.into_iter().collect()))?;
+
+        // Again, now that every mirror is known
+        import = MirrorStatus::from_sources(&ctx.paths, &mirrors, &branches, &comps)?;
+    }
+
+    for reason in &import.unmapped {
+        warn!("{reason}");
+    }
+
+    if import.status.mirrors().is_empty() {
+        bail!(fl!("no-known-mirror-in-sources"));
+    }
+
+    for path in &import.other_files {
+        warn!(
+            "{}",
+            fl!("import-other-file", path = path.display().to_string())
+        );
+    }
+
+    let mm = MirrorManager::with_status(import.status, ctx.paths.status_file());
+
+    match custom_file {
+        Some(s) => custom_mirrors::save(ctx, &s, Some(&mm))?,
+        None => ctx.apply(&mm, &branches, true)?,
+    }
+
+    Ok(())
+}
+
+/// Name a custom mirror after the host of its URL, e.g: `mirrors.example.org`.
+fn custom_mirror_name(url: &str, mirrors: &Mirrors, custom: &[(String, CustomMirror)]) -> String {
+    let base = Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_string()))
+        .unwrap_or_else(|| "custom".to_string());
+
+    let taken = |name: &str| mirrors.has(name) || custom.iter().any(|(n, _)| n == name);
+
+    let mut name = base.clone();
+    let mut i = 2;
+
+    while taken(&name) {
+        name = format!("{base}-{i}");
+        i += 1;
+    }
+
+    name
+}
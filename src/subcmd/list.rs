@@ -0,0 +1,106 @@
+use eyre::Result;
+use mirrormgr::mgr::{Branches, Comps, DistroConfig, MirrorManager};
+use serde::Serialize;
+use tabled::{settings::Style, Table, Tabled};
+
+use crate::{
+    args::{List, ListKind},
+    context::Context,
+    subcmd::display_enabled,
+};
+
+#[derive(Serialize, Tabled)]
+struct ListEntry {
+    #[tabled(rename = "Name")]
+    name: String,
+    #[serde(skip)]
+    #[tabled(rename = "Description")]
+    detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[tabled(skip)]
+    desc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[tabled(skip)]
+    url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[tabled(skip)]
+    location: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[tabled(skip)]
+    suites: Option<Vec<String>>,
+    #[tabled(rename = "Enabled", display_with = "display_enabled")]
+    enabled: bool,
+}
+
+pub fn execute(args: List, ctx: &Context) -> Result<()> {
+    let mm = MirrorManager::new(ctx.paths.status_file())?;
+    let status = mm.status();
+
+    let mut entries = match args.kind {
+        ListKind::Mirrors => {
+            let mirrors = ctx.mirrors()?;
+            ctx.warn_retired(&mm, &mirrors);
+            mirrors
+                .list_mirrors()
+                .iter()
+                .map(|m| {
+                    let (name, info) = m.inner();
+                    ListEntry {
+                        name: name.to_string(),
+                        detail: format!("{} ({})", info.desc(), info.url()),
+                        desc: Some(info.desc().to_string()),
+                        url: Some(info.url().to_string()),
+                        location: info.location().map(|x| x.to_string()),
+                        suites: None,
+                        enabled: status.mirrors().contains_key(name),
+                    }
+                })
+                .collect::<Vec<_>>()
+        }
+        ListKind::Branches => {
+            let branches = Branches::from_path(ctx.paths.branches())?;
+            branches
+                .list_branches()
+                .iter()
+                .map(|(name, info)| ListEntry {
+                    name: name.to_string(),
+                    detail: info.suites().join(" "),
+                    desc: None,
+                    url: None,
+                    location: None,
+                    suites: Some(info.suites().to_vec()),
+                    enabled: status.branch() == *name,
+                })
+                .collect()
+        }
+        ListKind::Components => {
+            let comps = Comps::from_path(ctx.paths.components())?;
+            comps
+                .list_comps()
+                .iter()
+                .map(|(name, desc)| ListEntry {
+                    name: name.to_string(),
+                    detail: desc.to_string(),
+                    desc: Some(desc.to_string()),
+                    url: None,
+                    location: None,
+                    suites: None,
+                    enabled: status.components().iter().any(|c| c == name),
+                })
+                .collect()
+        }
+    };
+
+    entries.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+
+    let mut t = Table::new(entries);
+    t.with(Style::psql());
+    println!("{t}");
+
+    Ok(())
+}
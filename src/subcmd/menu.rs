@@ -6,20 +6,26 @@ use inquire::{
 };
 use oma_console::WRITER;
 
-use crate::{
+use mirrormgr::{
     fl,
-    mgr::{Branches, DistroConfig, Mirror, MirrorManager},
-    utils::{create_status, distro_and_custom_mirrors, refresh, root},
-    APT_CONFIG, BRANCHES_PATH, STATUS_FILE,
+    mgr::{Branches, DistroConfig, Mirror},
+    speedtest::SpeedtestResults,
 };
 
-pub fn execute() -> Result<()> {
-    root()?;
-    let status = create_status(STATUS_FILE)?;
-    let mut mm = MirrorManager::new(status);
+use crate::{context::Context, subcmd::root};
 
-    let mm_info = distro_and_custom_mirrors()?;
-    let mirrors = mm_info.list_mirrors();
+pub fn execute(ctx: &Context) -> Result<()> {
+    root()?;
+    let _lock = ctx.lock()?;
+    let mut mm = ctx.load_status()?;
+
+    let mm_info = ctx.mirrors()?;
+    let results = SpeedtestResults::from_path(ctx.paths.speedtest_results()).unwrap_or_default();
+    let mirrors = mm_info
+        .list_mirrors()
+        .into_iter()
+        .map(|m| m.with_speed(&results))
+        .collect::<Vec<_>>();
 
     let mut default = vec![];
 
@@ -96,12 +102,12 @@ pub fn execute() -> Result<()> {
         mm.remove_mirrors(&remove_mirrors)?;
     }
 
-    let branches = Branches::from_path(BRANCHES_PATH)?;
-    mm.apply_config(&branches, APT_CONFIG)?;
-
-    if !add_mirrors.is_empty() || !remove_mirrors.is_empty() {
-        refresh()?;
-    }
+    let branches = Branches::from_path(ctx.paths.branches())?;
+    ctx.apply(
+        &mm,
+        &branches,
+        !add_mirrors.is_empty() || !remove_mirrors.is_empty(),
+    )?;
 
     Ok(())
 }
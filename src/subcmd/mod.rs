@@ -1,8 +1,47 @@
+use std::process::{exit, Command};
+
+use eyre::{anyhow, Result};
+use mirrormgr::fl;
+use rustix::process;
+
 pub mod add;
 pub mod custom_mirrors;
+pub mod doctor;
+pub mod history;
+pub mod import;
+pub mod list;
 pub mod menu;
 pub mod remove;
 pub mod reset;
 pub mod set;
 pub mod sort_mirrors;
 pub mod speedtest;
+pub mod status;
+pub mod sync;
+pub mod undo;
+pub mod verify;
+
+/// Mark enabled items in the `Enabled` column of a table.
+pub(crate) fn display_enabled(enabled: &bool) -> String {
+    if *enabled { "*" } else { "" }.to_string()
+}
+
+pub fn root() -> Result<()> {
+    if process::geteuid().is_root() {
+        return Ok(());
+    }
+
+    let args = std::env::args().collect::<Vec<_>>();
+
+    let out = Command::new("pkexec")
+        .args(args)
+        .spawn()
+        .and_then(|x| x.wait_with_output())
+        .map_err(|e| anyhow!(fl!("execute-pkexec-fail", e = e.to_string())))?;
+
+    exit(
+        out.status
+            .code()
+            .expect("Can not get pkexec oma exit status"),
+    );
+}
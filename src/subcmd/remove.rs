@@ -1,18 +1,13 @@
 use eyre::Result;
-use tracing::info;
 
-use crate::{
-    args::NormalArgs,
-    fl,
-    mgr::{Branches, DistroConfig, MirrorManager},
-    utils::{create_status, refresh, root},
-    APT_CONFIG, BRANCHES_PATH, STATUS_FILE,
-};
+use mirrormgr::mgr::{Branches, DistroConfig};
 
-pub fn execute(args: NormalArgs) -> Result<()> {
+use crate::{args::NormalArgs, context::Context, subcmd::root};
+
+pub fn execute(args: NormalArgs, ctx: &Context) -> Result<()> {
     root()?;
-    let status = create_status(STATUS_FILE)?;
-    let mut mm = MirrorManager::new(status);
+    let _lock = ctx.lock()?;
+    let mut mm = ctx.load_status()?;
 
     if let Some(mirrors) = args.mirrors {
         mm.remove_mirrors(&mirrors)?;
@@ -22,13 +17,9 @@ pub fn execute(args: NormalArgs) -> Result<()> {
         mm.remove_components(comps)?;
     }
 
-    let branches = Branches::from_path(BRANCHES_PATH)?;
-
-    info!("{}", fl!("write-sources"));
-    mm.apply_config(&branches, APT_CONFIG)?;
+    let branches = Branches::from_path(ctx.paths.branches())?;
 
-    info!("{}", fl!("run-refresh"));
-    refresh()?;
+    ctx.apply(&mm, &branches, true)?;
 
     Ok(())
 }
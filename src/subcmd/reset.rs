@@ -1,24 +1,16 @@
 use eyre::Result;
-use tracing::info;
 
-use crate::{
-    fl,
-    mgr::{Branches, DistroConfig, MirrorManager},
-    utils::{create_status, refresh, root},
-    APT_CONFIG, BRANCHES_PATH, STATUS_FILE,
-};
+use mirrormgr::mgr::{Branches, DistroConfig, MirrorManager};
 
-pub fn execute() -> Result<()> {
-    root()?;
-    let status = create_status(STATUS_FILE)?;
-    let mm = MirrorManager::reset(status);
-    let branches = Branches::from_path(BRANCHES_PATH)?;
+use crate::{context::Context, subcmd::root};
 
-    info!("{}", fl!("write-sources"));
-    mm.apply_config(&branches, APT_CONFIG)?;
+pub fn execute(ctx: &Context) -> Result<()> {
+    root()?;
+    let _lock = ctx.lock()?;
+    let mm = MirrorManager::reset(ctx.paths.status_file());
+    let branches = Branches::from_path(ctx.paths.branches())?;
 
-    info!("{}", fl!("run-refresh"));
-    refresh()?;
+    ctx.apply(&mm, &branches, true)?;
 
     Ok(())
 }
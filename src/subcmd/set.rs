@@ -1,22 +1,38 @@
 use crate::{
-    args::Set,
-    fl,
-    mgr::{Branches, DistroConfig, MirrorManager},
-    utils::{create_status, distro_and_custom_mirrors, refresh, root},
-    APT_CONFIG, BRANCHES_PATH, STATUS_FILE,
+    args::{Set, SourcesFormatArg},
+    context::Context,
+    subcmd::{
+        root,
+        speedtest::{self, SpeedtestOptions},
+    },
 };
 use eyre::Result;
+use mirrormgr::{
+    fl,
+    mgr::{Branches, DistroConfig, SourcesFormat},
+};
 use tracing::info;
 
-pub fn execute(args: Set) -> Result<()> {
+pub fn execute(args: Set, ctx: &Context) -> Result<()> {
     root()?;
-    let status_file = create_status(STATUS_FILE)?;
-    let mut mm = MirrorManager::new(status_file);
-    let branches = Branches::from_path(BRANCHES_PATH)?;
+    let _lock = ctx.lock()?;
+    let mut mm = ctx.load_status()?;
+    let branches = Branches::from_path(ctx.paths.branches())?;
 
     if let Some(mirror) = args.mirror {
-        let mirrors = distro_and_custom_mirrors()?;
+        let mirrors = ctx.mirrors()?;
         mm.set_mirror(&mirror, &mirrors)?;
+        // The name typed may be an alias or a replaced mirror
+        let mirror = mm.list_enabled_mirrors()[0];
+        info!("{}", fl!("set-mirror", mirror = mirror));
+    }
+
+    if args.fastest {
+        let mirrors = ctx.mirrors()?;
+        let opts = SpeedtestOptions::fastest(args.jobs, args.timeout);
+        let fastest = speedtest::fastest(ctx, &mirrors, 1, args.min_speed, &opts)?;
+        mm.set_mirror(&fastest[0], &mirrors)?;
+        let mirror = mm.list_enabled_mirrors()[0];
         info!("{}", fl!("set-mirror", mirror = mirror));
     }
 
@@ -25,11 +41,21 @@ pub fn execute(args: Set) -> Result<()> {
         info!("Branch is set to {branch}");
     }
 
-    info!("{}", fl!("write-sources"));
-    mm.apply_config(&branches, APT_CONFIG)?;
+    if let Some(format) = args.format {
+        let format = match format {
+            SourcesFormatArg::OneLine => SourcesFormat::OneLine,
+            SourcesFormatArg::Deb822 => SourcesFormat::Deb822,
+        };
+        if mm.set_format(format) {
+            info!("{}", fl!("set-format", format = format.to_string()));
+        }
+    }
+
+    if let Some(signed_by) = args.signed_by {
+        mm.set_signed_by(Some(signed_by).filter(|x| !x.is_empty()));
+    }
 
-    info!("{}", fl!("run-refresh"));
-    refresh()?;
+    ctx.apply(&mm, &branches, true)?;
 
     Ok(())
 }
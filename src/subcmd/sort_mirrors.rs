@@ -2,20 +2,16 @@ use std::process::exit;
 
 use dialoguer::{console::Term, theme::ColorfulTheme, Sort};
 use eyre::Result;
-use tracing::info;
 
-use crate::{
-    fl,
-    mgr::{Branches, DistroConfig, MirrorManager},
-    utils::{create_status, root},
-    APT_CONFIG, BRANCHES_PATH, STATUS_FILE,
-};
+use mirrormgr::mgr::{Branches, DistroConfig};
 
-pub fn execute() -> Result<()> {
+use crate::{context::Context, subcmd::root};
+
+pub fn execute(ctx: &Context) -> Result<()> {
     root()?;
-    let status_file = create_status(STATUS_FILE)?;
-    let mut mm = MirrorManager::new(status_file);
-    let branches = Branches::from_path(BRANCHES_PATH)?;
+    let _lock = ctx.lock()?;
+    let mut mm = ctx.load_status()?;
+    let branches = Branches::from_path(ctx.paths.branches())?;
 
     ctrlc::set_handler(|| {
         let term = Term::stdout();
@@ -42,8 +38,7 @@ pub fn execute() -> Result<()> {
 
     mm.reorder_mirrors(res);
 
-    info!("{}", fl!("write-sources"));
-    mm.apply_config(&branches, APT_CONFIG)?;
+    ctx.apply(&mm, &branches, false)?;
 
     Ok(())
 }
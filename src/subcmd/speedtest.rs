@@ -1,25 +1,36 @@
 use std::cmp::Ordering;
+use std::fmt::Display;
+use std::future::Future;
 use std::io::Write;
 use std::time::Duration;
 use std::time::Instant;
 
-use crate::fl;
-use crate::utils::url_strip;
-use eyre::{anyhow, Result};
-use indexmap::IndexMap;
+use crate::args::Speedtest;
+use crate::context::Context;
+use crate::subcmd::speedtest::export::export;
+use crate::SPEEDTEST_FILE_CHECKSUM;
+use eyre::{anyhow, bail, OptionExt, Result};
 use indicatif::{ProgressBar, ProgressStyle};
+use mirrormgr::fl;
+use mirrormgr::utils::{now_secs, url_strip};
 use oma_console::console;
-use reqwest::blocking::Client;
+use reqwest::Client;
 use sha2::Digest;
 use sha2::Sha256;
-use tabled::settings::Style;
+use tabled::settings::object::Columns;
+use tabled::settings::{Disable, Style};
 use tabled::Table;
 use tabled::Tabled;
+use tokio::net::{lookup_host, TcpStream};
+use tokio::runtime::Builder;
+use tokio::task::JoinSet;
+use url::Url;
 
-use crate::utils::distro_and_custom_mirrors;
-use crate::SPEEDTEST_FILE_CHECKSUM;
+use mirrormgr::mgr::Mirrors;
+use mirrormgr::speedtest::{format_speed, SpeedRecord, SpeedtestResults};
+use tracing::warn;
 
-const FILE_SIZE_KIB: f32 = 1024.0;
+mod export;
 
 #[derive(Tabled)]
 struct MirrorScore {
@@ -27,102 +38,610 @@ struct MirrorScore {
     mirror_name: String,
     #[tabled(rename = "Score")]
     score: String,
+    #[tabled(rename = "Min")]
+    min: String,
+    #[tabled(rename = "Max")]
+    max: String,
+    #[tabled(rename = "Std dev")]
+    stddev: String,
+    #[tabled(rename = "Failed rounds")]
+    failed: String,
 }
 
-impl From<(String, String)> for MirrorScore {
-    fn from(value: (String, String)) -> Self {
+impl From<(String, SpeedStats)> for MirrorScore {
+    fn from(value: (String, SpeedStats)) -> Self {
+        let (name, stats) = value;
         MirrorScore {
-            mirror_name: value.0,
-            score: value.1,
+            mirror_name: name,
+            score: format_speed(stats.median),
+            min: format_speed(stats.min),
+            max: format_speed(stats.max),
+            stddev: format_speed(stats.stddev),
+            failed: format!("{}/{}", stats.failed, stats.rounds),
+        }
+    }
+}
+
+/// Throughput (KiB/s) of a mirror over all speedtest rounds.
+#[derive(Clone, Copy)]
+pub struct SpeedStats {
+    pub median: f32,
+    pub min: f32,
+    pub max: f32,
+    pub stddev: f32,
+    pub failed: usize,
+    pub rounds: usize,
+    /// Total time spent on successful rounds
+    pub elapsed: Duration,
+    /// Total bytes downloaded in successful rounds
+    pub bytes: u64,
+}
+
+impl SpeedStats {
+    /// `None` if no round succeeded.
+    fn new(samples: &[(Duration, u64)], rounds: usize) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        let mut speeds = samples
+            .iter()
+            .map(|(elapsed, bytes)| *bytes as f32 / 1024.0 / elapsed.as_secs_f32())
+            .collect::<Vec<_>>();
+
+        speeds.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+
+        let n = speeds.len();
+        let median = if n.is_multiple_of(2) {
+            (speeds[n / 2 - 1] + speeds[n / 2]) / 2.0
+        } else {
+            speeds[n / 2]
+        };
+        let mean = speeds.iter().sum::<f32>() / n as f32;
+        let variance = speeds.iter().map(|s| (s - mean).powi(2)).sum::<f32>() / n as f32;
+
+        Some(Self {
+            median,
+            min: speeds[0],
+            max: speeds[n - 1],
+            stddev: variance.sqrt(),
+            failed: rounds - n,
+            rounds,
+            elapsed: samples.iter().map(|(elapsed, _)| *elapsed).sum(),
+            bytes: samples.iter().map(|(_, bytes)| *bytes).sum(),
+        })
+    }
+}
+
+#[derive(Tabled)]
+struct MirrorLatency {
+    #[tabled(rename = "Mirror name")]
+    mirror_name: String,
+    #[tabled(rename = "Connect")]
+    connect: String,
+    #[tabled(rename = "Connection setup")]
+    conn_setup: String,
+    #[tabled(rename = "First byte")]
+    ttfb: String,
+}
+
+impl From<(String, Latency)> for MirrorLatency {
+    fn from(value: (String, Latency)) -> Self {
+        let (name, latency) = value;
+        MirrorLatency {
+            mirror_name: name,
+            connect: format_duration(latency.connect),
+            conn_setup: format_duration(latency.conn_setup),
+            ttfb: format_duration(latency.ttfb),
+        }
+    }
+}
+
+/// Result of probing one mirror.
+pub struct Probe<T> {
+    pub name: String,
+    pub url: String,
+    pub result: Result<T>,
+    pub timestamp: u64,
+}
+
+/// The mirror was not tested before the time budget ran out.
+#[derive(Debug)]
+struct Untested(String);
+
+impl Display for Untested {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&fl!("mirror-untested", mirror = self.0.as_str()))
+    }
+}
+
+impl std::error::Error for Untested {}
+
+/// The mirror returned test data with a wrong checksum.
+#[derive(Debug)]
+struct ChecksumMismatch(String);
+
+impl Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&fl!("mirror-error", mirror = self.0.as_str()))
+    }
+}
+
+impl std::error::Error for ChecksumMismatch {}
+
+/// Latency of a mirror, measured without downloading the test file.
+#[derive(Clone, Copy)]
+struct Latency {
+    /// TCP connect time
+    connect: Duration,
+    /// Extra time the first request took to get a ready connection, from DNS lookup on. The
+    /// steps are not timed one by one.
+    conn_setup: Duration,
+    /// Time to first byte of a request on an established connection
+    ttfb: Duration,
+}
+
+pub struct SpeedtestOptions {
+    /// Mirrors tested at the same time
+    pub jobs: usize,
+    /// Timeout of each mirror
+    pub timeout: Duration,
+    /// Time limit of the whole run
+    pub budget: Option<Duration>,
+    /// Downloads of the test file per mirror
+    pub rounds: usize,
+    /// Hide the progress bar and per-mirror results
+    pub quiet: bool,
+}
+
+impl Default for SpeedtestOptions {
+    fn default() -> Self {
+        Self {
+            jobs: 4,
+            timeout: Duration::from_secs(10),
+            budget: None,
+            rounds: 1,
+            quiet: false,
+        }
+    }
+}
+
+impl SpeedtestOptions {
+    /// Options of `--fastest`, with the progress shown and a single round.
+    pub fn fastest(jobs: u16, timeout: u64) -> Self {
+        Self {
+            jobs: jobs as usize,
+            timeout: Duration::from_secs(timeout),
+            ..Default::default()
         }
     }
 }
 
-pub fn execute() -> Result<()> {
-    let mirrors = distro_and_custom_mirrors()?;
-    let map = mirrors.list_mirrors();
+impl From<&Speedtest> for SpeedtestOptions {
+    fn from(value: &Speedtest) -> Self {
+        Self {
+            jobs: value.jobs as usize,
+            timeout: Duration::from_secs(value.timeout),
+            budget: value.budget.map(Duration::from_secs),
+            rounds: value.rounds as usize,
+            quiet: value.format.is_some(),
+        }
+    }
+}
+
+pub fn execute(args: Speedtest, ctx: &Context) -> Result<()> {
+    let mirrors = ctx.mirrors()?;
+    let opts = SpeedtestOptions::from(&args);
+    let mut list = mirror_list(&mirrors);
+
+    if args.latency || args.prefilter.is_some() {
+        let latency = measure_latency(&list, &opts)?;
+
+        if let Some(count) = args.prefilter {
+            list = latency
+                .into_iter()
+                .filter(|p| p.result.is_ok())
+                .take(count)
+                .map(|p| (p.name, p.url))
+                .collect();
+        } else {
+            let all_latency = latency
+                .into_iter()
+                .filter_map(|p| Some((p.name, p.result.ok()?)))
+                .map(MirrorLatency::from);
+
+            let mut t = Table::new(all_latency);
+            t.with(Style::psql());
+
+            println!();
+            println!("{t}");
+
+            return Ok(());
+        }
+    }
+
+    let all_score = measure(ctx, &list, &opts)?;
+
+    if let Some(format) = args.format {
+        print!("{}", export(&all_score, format)?);
+        return Ok(());
+    }
+
+    let all_score = all_score
+        .into_iter()
+        .filter_map(|p| Some((p.name, p.result.ok()?)))
+        .map(MirrorScore::from);
+
+    let mut t = Table::new(all_score);
+    t.with(Style::psql());
+
+    if opts.rounds == 1 {
+        t.with(Disable::column(Columns::new(2..)));
+    }
+
+    println!();
+    println!("{t}");
+
+    Ok(())
+}
+
+/// Names of the `count` fastest mirrors reaching `min_speed` KiB/s, fastest first.
+pub fn fastest(
+    ctx: &Context,
+    mirrors: &Mirrors,
+    count: usize,
+    min_speed: f32,
+    opts: &SpeedtestOptions,
+) -> Result<Vec<String>> {
+    let res = measure(ctx, &mirror_list(mirrors), opts)?
+        .into_iter()
+        .filter(|p| p.result.as_ref().is_ok_and(|s| s.median >= min_speed))
+        .take(count)
+        .map(|p| p.name)
+        .collect::<Vec<_>>();
+
+    if res.is_empty() {
+        bail!(fl!(
+            "no-mirror-fast-enough",
+            speed = format_speed(min_speed)
+        ));
+    }
+
+    if res.len() < count {
+        warn!(
+            "{}",
+            fl!(
+                "not-enough-fast-mirrors",
+                count = res.len(),
+                speed = format_speed(min_speed)
+            )
+        );
+    }
+
+    Ok(res)
+}
+
+fn mirror_list(mirrors: &Mirrors) -> Vec<(String, String)> {
+    mirrors
+        .list_mirrors()
+        .iter()
+        .map(|m| {
+            let (name, info) = m.inner();
+            (name.to_owned(), info.url().to_owned())
+        })
+        .collect()
+}
+
+/// Speedtest all mirrors, returning their speed, fastest (by median) first.
+/// Mirrors that failed every round or returned mismatched test data come last.
+/// Results are saved so that `menu` and `status` can show them.
+fn measure(
+    ctx: &Context,
+    list: &[(String, String)],
+    opts: &SpeedtestOptions,
+) -> Result<Vec<Probe<SpeedStats>>> {
+    let client = Client::builder()
+        .timeout(opts.timeout)
+        .user_agent("AOSC mirrormgr")
+        .build()?;
+
+    let rounds = opts.rounds;
+    let timeout = opts.timeout;
+
+    let mut all_score = run_probes(
+        list,
+        opts,
+        move |name, url| {
+            let client = client.clone();
+            async move {
+                let mut samples = vec![];
+                let mut last_err = None;
+
+                // The timeout covers all rounds of a mirror, so that slow ones can't take
+                // `rounds` times longer
+                let deadline = tokio::time::Instant::now() + timeout;
+
+                // Rounds of the same mirror run one after another, so they don't compete for bandwidth
+                for _ in 0..rounds {
+                    match tokio::time::timeout_at(deadline, get_score(&client, &name, &url)).await {
+                        Ok(Ok(s)) => samples.push(s),
+                        Ok(Err(e)) => last_err = Some(e),
+                        Err(e) => {
+                            last_err = Some(e.into());
+                            break;
+                        }
+                    }
+                }
+
+                SpeedStats::new(&samples, rounds)
+                    .ok_or_else(|| last_err.unwrap_or_else(|| anyhow!("No speedtest round")))
+            }
+        },
+        |stats| {
+            if stats.rounds == 1 {
+                format_speed(stats.median)
+            } else {
+                format!(
+                    "{} ({} failed rounds)",
+                    format_speed(stats.median),
+                    stats.failed
+                )
+            }
+        },
+    )?;
+
+    all_score.sort_by(|p1, p2| {
+        let s1 = p1.result.as_ref().ok().map(|s| s.median);
+        let s2 = p2.result.as_ref().ok().map(|s| s.median);
+        s2.partial_cmp(&s1).unwrap_or(Ordering::Equal)
+    });
 
-    let bar = ProgressBar::new(map.len() as u64);
+    if !ctx.dry_run {
+        save_results(ctx, &all_score);
+    }
+
+    Ok(all_score)
+}
+
+/// Probe the latency of all mirrors, lowest time to first byte first.
+fn measure_latency(
+    list: &[(String, String)],
+    opts: &SpeedtestOptions,
+) -> Result<Vec<Probe<Latency>>> {
+    let timeout = opts.timeout;
+
+    let mut all_latency = run_probes(
+        list,
+        opts,
+        move |_, url| async move { get_latency(timeout, &url).await },
+        |latency| {
+            format!(
+                "{} ({})",
+                format_duration(latency.ttfb),
+                format_duration(latency.connect)
+            )
+        },
+    )?;
+
+    all_latency.sort_by_key(|p| p.result.as_ref().map_or(Duration::MAX, |l| l.ttfb));
+
+    Ok(all_latency)
+}
+
+/// Run `probe` on every mirror with at most `opts.jobs` of them at the same time.
+/// Mirrors not tested within the time budget fail with [`Untested`].
+fn run_probes<T, F, Fut>(
+    list: &[(String, String)],
+    opts: &SpeedtestOptions,
+    probe: F,
+    describe: fn(&T) -> String,
+) -> Result<Vec<Probe<T>>>
+where
+    T: Send + 'static,
+    F: Fn(String, String) -> Fut,
+    Fut: Future<Output = Result<T>> + Send + 'static,
+{
+    let bar = if opts.quiet {
+        ProgressBar::hidden()
+    } else {
+        ProgressBar::new(list.len() as u64)
+    };
     bar.set_style(
         ProgressStyle::with_template("[{wide_bar:.cyan/blue}] ({pos}/{len})")
             .unwrap()
             .progress_chars("=>-"),
     );
 
-    let mut all_score = IndexMap::new();
-    let client = reqwest::blocking::Client::builder()
-        .timeout(Duration::from_secs(10))
-        .user_agent("AOSC mirrormgr")
-        .build()?;
+    let runtime = Builder::new_multi_thread().enable_all().build()?;
+
+    let mut untested = vec![];
+    let mut res = runtime.block_on(async {
+        let deadline = opts.budget.map(|b| tokio::time::Instant::now() + b);
+        let mut pending = list.iter().cloned();
+        let mut tasks = JoinSet::new();
+        let mut res = vec![];
+
+        loop {
+            while tasks.len() < opts.jobs {
+                let Some((name, url)) = pending.next() else {
+                    break;
+                };
+
+                let fut = probe(name.clone(), url.clone());
+                tasks.spawn(async move { (name, url, fut.await) });
+            }
+
+            let next = match deadline {
+                Some(deadline) => {
+                    match tokio::time::timeout_at(deadline, tasks.join_next()).await {
+                        Ok(next) => next,
+                        Err(_) => {
+                            // Aborted tasks can't tell which mirror they were testing
+                            let done = res
+                                .iter()
+                                .map(|p: &Probe<T>| p.name.as_str())
+                                .collect::<Vec<_>>();
+                            untested = list
+                                .iter()
+                                .filter(|(name, _)| !done.contains(&name.as_str()))
+                                .cloned()
+                                .collect();
+                            tasks.abort_all();
+                            break;
+                        }
+                    }
+                }
+                None => tasks.join_next().await,
+            };
+
+            let Some(next) = next else {
+                break;
+            };
+
+            let (name, url, result) = next?;
 
-    for i in map {
-        let (name, info) = i.inner();
-        let name = name.to_owned();
-        let info = info.to_owned();
-        let score = get_score(&client, &name, info.url());
-
-        match score {
-            Ok(s) => {
-                let score = FILE_SIZE_KIB / s;
-                bar.println(
-                    console::style(format!("{name}: {}", format_speed(score)))
+            match &result {
+                Ok(r) => bar.println(
+                    console::style(format!("{name}: {}", describe(r)))
                         .green()
                         .to_string(),
-                );
-                all_score.insert(name, score);
-            }
-            Err(e) => {
-                bar.println(
+                ),
+                Err(e) => bar.println(
                     console::style(format!("{name}: {}", e.chain().last().unwrap()))
                         .red()
                         .to_string(),
-                );
+                ),
             }
+
+            res.push(Probe {
+                name,
+                url,
+                result,
+                timestamp: now_secs(),
+            });
+            bar.inc(1);
         }
-        bar.inc(1);
-    }
+
+        Ok::<_, eyre::Report>(res)
+    })?;
 
     bar.finish_and_clear();
 
-    let all_score = all_score
-        .sorted_unstable_by(|_, s1, _, s2| s2.partial_cmp(s1).unwrap_or(Ordering::Equal))
-        .map(|(x, y)| (x.to_owned(), format_speed(y)))
-        .map(MirrorScore::from);
+    if !untested.is_empty() {
+        warn!(
+            "{}",
+            fl!("speedtest-budget-exceeded", count = untested.len())
+        );
+    }
 
-    let mut t = Table::new(all_score);
-    t.with(Style::psql());
+    let timestamp = now_secs();
+    res.extend(untested.into_iter().map(|(name, url)| Probe {
+        result: Err(Untested(name.clone()).into()),
+        name,
+        url,
+        timestamp,
+    }));
 
-    println!();
-    println!("{t}");
-
-    Ok(())
+    Ok(res)
 }
 
-fn get_score(client: &Client, name: &str, url: &str) -> Result<f32> {
+/// Download the test file, returning the time it took and its size.
+async fn get_score(client: &Client, name: &str, url: &str) -> Result<(Duration, u64)> {
     let timer = Instant::now();
     let buf = client
         .get(format!("{}.repotest", url_strip(url)))
-        .send()?
-        .bytes()?;
+        .send()
+        .await?
+        .bytes()
+        .await?;
 
     let mut hasher = Sha256::new();
     hasher.write_all(&buf)?;
     let c = hex::encode(hasher.finalize());
     if c == SPEEDTEST_FILE_CHECKSUM {
-        let result_time = timer.elapsed().as_secs_f32();
-        return Ok(result_time);
+        return Ok((timer.elapsed(), buf.len() as u64));
     }
 
-    Err(anyhow!(fl!("mirror-error", mirror = name.to_string())))
+    Err(ChecksumMismatch(name.to_string()).into())
 }
 
-fn format_speed(score: f32) -> String {
-    let mut score = score;
-    let mut unit = "KiB/s";
-    if score > 1000.0 {
-        score /= 1024.0;
-        unit = "MiB/s";
+async fn get_latency(timeout: Duration, url: &str) -> Result<Latency> {
+    let test_url = format!("{}.repotest", url_strip(url));
+    let parsed = Url::parse(&test_url)?;
+    let host = parsed.host_str().ok_or_eyre("URL has no host")?;
+    let port = parsed
+        .port_or_known_default()
+        .ok_or_eyre("URL has no port")?;
+
+    let addr = tokio::time::timeout(timeout, lookup_host((host, port)))
+        .await??
+        .next()
+        .ok_or_eyre("Host has no address")?;
+
+    let timer = Instant::now();
+    tokio::time::timeout(timeout, TcpStream::connect(addr)).await??;
+    let connect = timer.elapsed();
+
+    // A fresh client per mirror, so the first request has to set up a new connection
+    // and the second one reuses it
+    let client = Client::builder()
+        .timeout(timeout)
+        .user_agent("AOSC mirrormgr")
+        .build()?;
+
+    let timer = Instant::now();
+    first_byte(&client, &test_url).await?;
+    let cold = timer.elapsed();
+
+    let timer = Instant::now();
+    first_byte(&client, &test_url).await?;
+    let ttfb = timer.elapsed();
+
+    // Both requests go through the same connection, so the difference is its setup
+    let conn_setup = cold.saturating_sub(ttfb);
+
+    Ok(Latency {
+        connect,
+        conn_setup,
+        ttfb,
+    })
+}
+
+/// Send a HEAD request for `url`, returning once the response headers arrived. There is no
+/// body to read, even from mirrors ignoring ranges, and the connection can be reused.
+async fn first_byte(client: &Client, url: &str) -> Result<()> {
+    client.head(url).send().await?.error_for_status()?;
+
+    Ok(())
+}
+
+fn save_results(ctx: &Context, all_score: &[Probe<SpeedStats>]) {
+    let path = ctx.paths.speedtest_results();
+    let mut results = SpeedtestResults::from_path(&path).unwrap_or_default();
+
+    for p in all_score {
+        // Keep the previous result of mirrors that were not tested this time
+        if p.result.as_ref().is_err_and(|e| e.is::<Untested>()) {
+            continue;
+        }
+
+        let speed = p.result.as_ref().ok().map(|s| s.median);
+        results.insert(p.name.to_owned(), SpeedRecord::new(speed));
+    }
+
+    // Not being able to save results (e.g: not running as root) should not fail the speedtest
+    if let Err(e) = results.write(&path) {
+        warn!(
+            "{}",
+            fl!(
+                "save-speedtest-failed",
+                path = path.display().to_string(),
+                e = e.to_string()
+            )
+        );
     }
+}
 
-    format!("{:.2}{}", score, unit)
+fn format_duration(d: Duration) -> String {
+    format!("{:.1}ms", d.as_secs_f64() * 1000.0)
 }
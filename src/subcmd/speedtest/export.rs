@@ -0,0 +1,299 @@
+use std::fmt::Write;
+
+use eyre::Result;
+use serde::Serialize;
+
+use crate::args::ExportFormat;
+
+use super::{ChecksumMismatch, Probe, SpeedStats, Untested};
+
+/// One exported speedtest result.
+#[derive(Serialize)]
+struct Record<'a> {
+    name: &'a str,
+    url: &'a str,
+    /// KiB/s, median of all successful rounds
+    throughput: Option<f32>,
+    /// Seconds spent on successful rounds
+    elapsed: Option<f64>,
+    /// Bytes downloaded in successful rounds
+    bytes: Option<u64>,
+    error: Option<ErrorKind>,
+    timestamp: u64,
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+enum ErrorKind {
+    Timeout,
+    Connect,
+    Http,
+    Checksum,
+    Untested,
+    Other,
+}
+
+impl ErrorKind {
+    fn new(e: &eyre::Report) -> Self {
+        for cause in e.chain() {
+            if cause.is::<Untested>() {
+                return Self::Untested;
+            }
+
+            if cause.is::<ChecksumMismatch>() {
+                return Self::Checksum;
+            }
+
+            if cause.is::<tokio::time::error::Elapsed>() {
+                return Self::Timeout;
+            }
+
+            if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+                if e.is_timeout() {
+                    return Self::Timeout;
+                }
+
+                if e.is_connect() {
+                    return Self::Connect;
+                }
+
+                if e.is_status() {
+                    return Self::Http;
+                }
+            }
+        }
+
+        Self::Other
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Timeout => "timeout",
+            Self::Connect => "connect",
+            Self::Http => "http",
+            Self::Checksum => "checksum",
+            Self::Untested => "untested",
+            Self::Other => "other",
+        }
+    }
+}
+
+impl<'a> From<&'a Probe<SpeedStats>> for Record<'a> {
+    fn from(p: &'a Probe<SpeedStats>) -> Self {
+        let stats = p.result.as_ref().ok();
+
+        Record {
+            name: &p.name,
+            url: &p.url,
+            throughput: stats.map(|s| s.median),
+            elapsed: stats.map(|s| s.elapsed.as_secs_f64()),
+            bytes: stats.map(|s| s.bytes),
+            error: p.result.as_ref().err().map(ErrorKind::new),
+            timestamp: p.timestamp,
+        }
+    }
+}
+
+/// Format speedtest results as `format`, one record per mirror.
+pub fn export(all_score: &[Probe<SpeedStats>], format: ExportFormat) -> Result<String> {
+    let records = all_score.iter().map(Record::from).collect::<Vec<_>>();
+
+    let s = match format {
+        ExportFormat::Json => serde_json::to_string_pretty(&records)? + "\n",
+        ExportFormat::Csv => csv(&records),
+        ExportFormat::OpenMetrics => openmetrics(&records),
+    };
+
+    Ok(s)
+}
+
+fn csv(records: &[Record]) -> String {
+    let mut s = String::from("name,url,throughput,elapsed,bytes,error,timestamp\n");
+
+    for r in records {
+        let _ = writeln!(
+            s,
+            "{},{},{},{},{},{},{}",
+            csv_field(r.name),
+            csv_field(r.url),
+            r.throughput.map(|x| x.to_string()).unwrap_or_default(),
+            r.elapsed.map(|x| x.to_string()).unwrap_or_default(),
+            r.bytes.map(|x| x.to_string()).unwrap_or_default(),
+            r.error.map(|x| x.as_str()).unwrap_or_default(),
+            r.timestamp
+        );
+    }
+
+    s
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn openmetrics(records: &[Record]) -> String {
+    let mut s = String::new();
+
+    // (name, unit, help, value)
+    type Metric = (
+        &'static str,
+        &'static str,
+        &'static str,
+        fn(&Record) -> Option<f64>,
+    );
+
+    let metrics: [Metric; 5] = [
+        (
+            "mirrormgr_speedtest_throughput_bytes_per_second",
+            "bytes_per_second",
+            "Median download throughput of the test file.",
+            |r| r.throughput.map(|x| f64::from(x) * 1024.0),
+        ),
+        (
+            "mirrormgr_speedtest_elapsed_seconds",
+            "seconds",
+            "Time spent on successful downloads.",
+            |r| r.elapsed,
+        ),
+        (
+            "mirrormgr_speedtest_downloaded_bytes",
+            "bytes",
+            "Bytes downloaded in successful rounds.",
+            |r| r.bytes.map(|x| x as f64),
+        ),
+        (
+            "mirrormgr_speedtest_success",
+            "",
+            "Whether the mirror passed the speedtest.",
+            |r| Some(if r.error.is_none() { 1.0 } else { 0.0 }),
+        ),
+        (
+            "mirrormgr_speedtest_timestamp_seconds",
+            "seconds",
+            "When the mirror was tested.",
+            |r| Some(r.timestamp as f64),
+        ),
+    ];
+
+    for (name, unit, help, value) in metrics {
+        let _ = writeln!(s, "# TYPE {name} gauge");
+        if !unit.is_empty() {
+            let _ = writeln!(s, "# UNIT {name} {unit}");
+        }
+        let _ = writeln!(s, "# HELP {name} {help}");
+
+        for r in records {
+            let Some(value) = value(r) else {
+                continue;
+            };
+
+            let mut labels = format!(
+                "mirror=\"{}\",url=\"{}\"",
+                label_value(r.name),
+                label_value(r.url)
+            );
+
+            if let Some(e) = r.error {
+                let _ = write!(labels, ",error=\"{}\"", e.as_str());
+            }
+
+            let _ = writeln!(s, "{name}{{{labels}}} {value}");
+        }
+    }
+
+    s.push_str("# EOF\n");
+
+    s
+}
+
+fn label_value(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn probes() -> Vec<Probe<SpeedStats>> {
+        vec![
+            Probe {
+                name: "fast".to_string(),
+                url: "https://fast.example/aosc/".to_string(),
+                result: Ok(SpeedStats {
+                    median: 2.0,
+                    min: 1.0,
+                    max: 3.0,
+                    stddev: 1.0,
+                    failed: 0,
+                    rounds: 3,
+                    elapsed: Duration::from_secs(3),
+                    bytes: 6144,
+                }),
+                timestamp: 1700000000,
+            },
+            Probe {
+                name: "odd,\"name\"".to_string(),
+                url: "https://odd.example/a\\b\n".to_string(),
+                result: Err(Untested("odd".to_string()).into()),
+                timestamp: 1700000001,
+            },
+        ]
+    }
+
+    #[test]
+    fn csv_escaping() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn csv_export() {
+        let s = export(&probes(), ExportFormat::Csv).unwrap();
+
+        assert_eq!(
+            s,
+            "\
+name,url,throughput,elapsed,bytes,error,timestamp
+fast,https://fast.example/aosc/,2,3,6144,,1700000000
+\"odd,\"\"name\"\"\",\"https://odd.example/a\\b\n\",,,,untested,1700000001
+"
+        );
+    }
+
+    #[test]
+    fn openmetrics_escaping() {
+        assert_eq!(label_value("plain"), "plain");
+        assert_eq!(label_value("a\\b"), "a\\\\b");
+        assert_eq!(label_value("say \"hi\""), "say \\\"hi\\\"");
+        assert_eq!(label_value("two\nlines"), "two\\nlines");
+    }
+
+    #[test]
+    fn openmetrics_export() {
+        let s = export(&probes(), ExportFormat::OpenMetrics).unwrap();
+
+        assert!(s.ends_with("# EOF\n"));
+        assert!(s.contains(
+            "mirrormgr_speedtest_throughput_bytes_per_second{mirror=\"fast\",url=\"https://fast.example/aosc/\"} 2048\n"
+        ));
+        assert!(s.contains(
+            "mirrormgr_speedtest_success{mirror=\"odd,\\\"name\\\"\",url=\"https://odd.example/a\\\\b\\n\",error=\"untested\"} 0\n"
+        ));
+        // Failed mirrors have no throughput
+        assert_eq!(
+            s.matches("mirrormgr_speedtest_throughput_bytes_per_second{")
+                .count(),
+            1
+        );
+    }
+}
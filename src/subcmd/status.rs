@@ -0,0 +1,96 @@
+use eyre::Result;
+use mirrormgr::{
+    fl,
+    mgr::MirrorManager,
+    speedtest::{SpeedRecord, SpeedtestResults},
+};
+use serde::Serialize;
+
+use crate::{args::Status, context::Context};
+
+#[derive(Serialize)]
+struct StatusOutput<'a> {
+    branch: &'a str,
+    components: &'a [String],
+    format: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signed_by: Option<&'a str>,
+    mirrors: Vec<MirrorOutput<'a>>,
+}
+
+#[derive(Serialize)]
+struct MirrorOutput<'a> {
+    name: &'a str,
+    url: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    speedtest: Option<SpeedtestOutput>,
+    #[serde(skip)]
+    record: Option<&'a SpeedRecord>,
+}
+
+#[derive(Serialize)]
+struct SpeedtestOutput {
+    /// KiB/s
+    speed: Option<f32>,
+    timestamp: u64,
+    stale: bool,
+}
+
+pub fn execute(args: Status, ctx: &Context) -> Result<()> {
+    let mm = MirrorManager::new(ctx.paths.status_file())?;
+    ctx.warn_retired(&mm, &ctx.mirrors()?);
+    let status = mm.status();
+    let results = SpeedtestResults::from_path(ctx.paths.speedtest_results()).unwrap_or_default();
+
+    let output = StatusOutput {
+        branch: status.branch(),
+        components: status.components(),
+        format: status.format().to_string(),
+        signed_by: status.signed_by(),
+        mirrors: status
+            .mirrors()
+            .iter()
+            .map(|(name, url)| {
+                let record = results.get(name);
+                MirrorOutput {
+                    name,
+                    url,
+                    speedtest: record.map(|r| SpeedtestOutput {
+                        speed: r.speed(),
+                        timestamp: r.timestamp(),
+                        stale: r.is_stale(),
+                    }),
+                    record,
+                }
+            })
+            .collect(),
+    };
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return Ok(());
+    }
+
+    println!("{}: {}", fl!("status-branch"), output.branch);
+    println!(
+        "{}: {}",
+        fl!("status-components"),
+        output.components.join(" ")
+    );
+    println!("{}: {}", fl!("status-format"), output.format);
+
+    if let Some(signed_by) = output.signed_by {
+        println!("Signed-By: {signed_by}");
+    }
+
+    println!("{}:", fl!("status-mirrors"));
+
+    for (i, m) in output.mirrors.iter().enumerate() {
+        match m.record {
+            Some(record) => println!("  {}. {} ({}) - {record}", i + 1, m.name, m.url),
+            None => println!("  {}. {} ({})", i + 1, m.name, m.url),
+        }
+    }
+
+    Ok(())
+}
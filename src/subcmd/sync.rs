@@ -0,0 +1,102 @@
+use std::io::{stderr, stdin, IsTerminal};
+
+use dialoguer::{console::Term, theme::ColorfulTheme, Select};
+use eyre::Result;
+use mirrormgr::{
+    fl,
+    mgr::{Branches, DistroConfig, MirrorManager, Mirrors},
+};
+use tracing::{info, warn};
+
+use crate::{
+    args::Sync,
+    context::Context,
+    subcmd::{doctor::remove_mirror, root},
+};
+
+pub fn execute(args: Sync, ctx: &Context) -> Result<()> {
+    root()?;
+    let _lock = ctx.lock()?;
+
+    let mut mm = ctx.load_status()?;
+    let mirrors = ctx.mirrors()?;
+    let branches = Branches::from_path(ctx.paths.branches())?;
+
+    let report = mm.sync_urls(&mirrors);
+    let mut changed = !report.updated.is_empty();
+
+    for (mirror, old, new) in &report.updated {
+        info!(
+            "{}",
+            fl!(
+                "sync-updated-url",
+                mirror = mirror.as_str(),
+                old = old.as_str(),
+                new = new.as_str()
+            )
+        );
+    }
+
+    let interactive = stdin().is_terminal() && stderr().is_terminal();
+
+    for mirror in &report.missing {
+        warn!(
+            "{}",
+            fl!("drift-mirror-not-found", mirror = mirror.as_str())
+        );
+    }
+
+    // Retired mirrors were already reported when loading the status
+    for mirror in report.missing.iter().chain(&report.retired) {
+        if args.yes {
+            remove_mirror(&mut mm, mirror, &mirrors)?;
+            changed = true;
+        } else if interactive {
+            changed |= migrate(&mut mm, mirror, &mirrors)?;
+        } else {
+            warn!("{}", fl!("sync-missing-kept", mirror = mirror.as_str()));
+        }
+    }
+
+    if !changed {
+        if report.missing.is_empty() && report.retired.is_empty() {
+            info!("{}", fl!("sync-up-to-date"));
+        }
+        return Ok(());
+    }
+
+    ctx.apply(&mm, &branches, true)?;
+
+    Ok(())
+}
+
+/// Ask what to use instead of `mirror`, which is no longer in the mirror list.
+fn migrate(mm: &mut MirrorManager, mirror: &str, mirrors: &Mirrors) -> Result<bool> {
+    let enabled = mm.list_enabled_mirrors();
+
+    let mut candidates = mirrors
+        .list_mirrors()
+        .into_iter()
+        .map(|m| m.inner().0.to_string())
+        .filter(|name| !enabled.contains(&name.as_str()))
+        .collect::<Vec<_>>();
+    candidates.sort_unstable();
+
+    let mut items = vec![fl!("sync-disable")];
+    items.extend(candidates.iter().cloned());
+    items.push(fl!("doctor-skip"));
+
+    let choice = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(fl!("sync-migrate-prompt", mirror = mirror))
+        .items(&items)
+        .default(0)
+        .interact_on(&Term::stderr())?;
+
+    match choice {
+        0 => remove_mirror(mm, mirror, mirrors)?,
+        i if i <= candidates.len() => mm.replace_mirror(mirror, &candidates[i - 1], mirrors)?,
+        _ => return Ok(false),
+    }
+
+    Ok(true)
+}
@@ -0,0 +1,34 @@
+use eyre::{bail, Result};
+use mirrormgr::{
+    fl,
+    history::History,
+    mgr::{Branches, DistroConfig, MirrorManager},
+};
+use tracing::info;
+
+use crate::{context::Context, subcmd::root};
+
+pub fn execute(ctx: &Context) -> Result<()> {
+    root()?;
+    let _lock = ctx.lock()?;
+
+    let history = History::from_path(ctx.paths.history()).unwrap_or_default();
+    let entries = history.entries();
+
+    if entries.len() < 2 {
+        bail!(fl!("nothing-to-undo"));
+    }
+
+    let entry = &entries[entries.len() - 2];
+    info!("{}", fl!("undo-to", id = entry.id()));
+
+    let mm = MirrorManager::with_status(entry.status().clone(), ctx.paths.status_file());
+    let branches = Branches::from_path(ctx.paths.branches())?;
+
+    // Drop the undone entry, so that undoing again goes further back
+    ctx.apply_with_history(&mm, &branches, true, |history, _| {
+        history.pop();
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use eyre::{bail, Result};
+use mirrormgr::{
+    fl,
+    mgr::{Branches, Comps, DistroConfig, MirrorManager},
+    verify::{verify, Finding},
+};
+use tracing::{info, warn};
+
+use crate::context::{print_diff, Context};
+
+pub fn execute(ctx: &Context) -> Result<()> {
+    let mm = MirrorManager::new(ctx.paths.status_file())?;
+    let mirrors = ctx.mirrors()?;
+    let branches = Branches::from_path(ctx.paths.branches())?;
+    let comps = Comps::from_path(ctx.paths.components())?;
+
+    let findings = verify(&mm, &mirrors, &branches, &comps, &ctx.paths)?;
+
+    if findings.is_empty() {
+        info!("{}", fl!("verify-ok"));
+        return Ok(());
+    }
+
+    for finding in &findings {
+        warn!("{finding}");
+
+        if let Finding::SourcesDrift { diff, .. } = finding {
+            print_diff(diff);
+        }
+    }
+
+    bail!(fl!("verify-found", count = findings.len()));
+}
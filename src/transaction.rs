@@ -0,0 +1,200 @@
+use std::{
+    fs::{self, File, Permissions},
+    io::{ErrorKind, Write},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tempfile::NamedTempFile;
+
+/// Replaces several files so that either all or none of the changes take effect.
+///
+/// New contents are first written to fsynced temporary files next to their targets. On
+/// commit, the list of pending renames and removals is saved to a journal before any target
+/// is touched, so a commit interrupted by a crash is finished by [`recover`] on the next run.
+pub struct Transaction {
+    journal: PathBuf,
+    ops: Vec<Op>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Op {
+    Write { target: PathBuf, tmp: PathBuf },
+    Remove { target: PathBuf },
+}
+
+impl Transaction {
+    pub fn new<P: Into<PathBuf>>(journal: P) -> Self {
+        Self {
+            journal: journal.into(),
+            ops: vec![],
+        }
+    }
+
+    /// Stage `contents` to replace `target`.
+    pub fn write<P: AsRef<Path>>(&mut self, target: P, contents: &[u8]) -> Result<()> {
+        let target = target.as_ref();
+        let tmp = write_temp(target, contents)?.keep()?;
+
+        self.ops.push(Op::Write {
+            target: target.to_path_buf(),
+            tmp,
+        });
+
+        Ok(())
+    }
+
+    /// Stage the removal of `target`, if it exists.
+    pub fn remove<P: AsRef<Path>>(&mut self, target: P) {
+        self.ops.push(Op::Remove {
+            target: target.as_ref().to_path_buf(),
+        });
+    }
+
+    pub fn commit(mut self) -> Result<()> {
+        let ops = std::mem::take(&mut self.ops);
+        write_atomic(&self.journal, &serde_json::to_vec(&ops)?)
+            .context("Can not write commit journal")?;
+
+        replay(&ops)?;
+        remove_journal(&self.journal)
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        // Not committed, throw away the staged files
+        for op in &self.ops {
+            if let Op::Write { tmp, .. } = op {
+                let _ = fs::remove_file(tmp);
+            }
+        }
+    }
+}
+
+/// Contents of a set of files at some point, to put them back later.
+pub struct Snapshot {
+    /// `None` if the file did not exist
+    files: Vec<(PathBuf, Option<Vec<u8>>)>,
+}
+
+impl Snapshot {
+    pub fn take<P: AsRef<Path>>(paths: &[P]) -> Result<Self> {
+        let mut files = vec![];
+
+        for path in paths {
+            let path = path.as_ref();
+            let contents = match fs::read(path) {
+                Ok(s) => Some(s),
+                Err(e) if e.kind() == ErrorKind::NotFound => None,
+                Err(e) => return Err(e).context(format!("Can not read {}", path.display())),
+            };
+
+            files.push((path.to_path_buf(), contents));
+        }
+
+        Ok(Self { files })
+    }
+
+    /// Put all files back as they were, removing those which did not exist.
+    pub fn restore<P: Into<PathBuf>>(&self, journal: P) -> Result<()> {
+        let mut tx = Transaction::new(journal);
+
+        for (path, contents) in &self.files {
+            match contents {
+                Some(contents) => tx.write(path, contents)?,
+                None => tx.remove(path),
+            }
+        }
+
+        tx.commit()
+    }
+}
+
+/// Finish a commit interrupted by a crash. Returns `false` if there was none.
+pub fn recover<P: AsRef<Path>>(journal: P) -> Result<bool> {
+    let journal = journal.as_ref();
+
+    let ops: Vec<Op> = match fs::read(journal) {
+        Ok(s) => serde_json::from_slice(&s).context("Can not parse commit journal")?,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+
+    replay(&ops)?;
+    remove_journal(journal)?;
+
+    Ok(true)
+}
+
+/// Replace `path` with `contents` through a fsynced temporary file and a rename.
+pub fn write_atomic<P: AsRef<Path>>(path: P, contents: &[u8]) -> Result<()> {
+    let path = path.as_ref();
+    write_temp(path, contents)?.persist(path)?;
+    sync_parent(path)
+}
+
+fn write_temp(target: &Path, contents: &[u8]) -> Result<tempfile::TempPath> {
+    let parent = parent(target);
+    fs::create_dir_all(parent)?;
+
+    let mut f = NamedTempFile::new_in(parent)
+        .with_context(|| format!("Can not create a temporary file in {}", parent.display()))?;
+    f.write_all(contents)?;
+
+    // Temporary files are only readable by the owner, but APT reads sources as _apt
+    let perm = match fs::metadata(target) {
+        Ok(m) => m.permissions(),
+        Err(_) => Permissions::from_mode(0o644),
+    };
+    f.as_file().set_permissions(perm)?;
+    f.as_file().sync_all()?;
+
+    Ok(f.into_temp_path())
+}
+
+fn replay(ops: &[Op]) -> Result<()> {
+    for op in ops {
+        match op {
+            Op::Write { target, tmp } => {
+                // Already renamed before an interruption
+                if !tmp.exists() {
+                    continue;
+                }
+
+                fs::rename(tmp, target)
+                    .with_context(|| format!("Can not write {}", target.display()))?;
+                sync_parent(target)?;
+            }
+            Op::Remove { target } => match fs::remove_file(target) {
+                Ok(()) => sync_parent(target)?,
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => {
+                    return Err(e).context(format!("Can not remove {}", target.display()));
+                }
+            },
+        }
+    }
+
+    Ok(())
+}
+
+fn remove_journal(journal: &Path) -> Result<()> {
+    fs::remove_file(journal)?;
+    sync_parent(journal)
+}
+
+fn sync_parent(path: &Path) -> Result<()> {
+    File::open(parent(path))?.sync_all()?;
+    Ok(())
+}
+
+fn parent(path: &Path) -> &Path {
+    match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    }
+}
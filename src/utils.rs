@@ -1,40 +1,18 @@
-use eyre::{anyhow, bail, Result};
-use rustix::process;
+use eyre::Result;
 use std::{
     borrow::Cow,
-    fs::{self, File},
-    path::Path,
-    process::{exit, Command},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use crate::{
-    fl,
-    mgr::{CustomMirrors, DistroConfig, Mirrors},
-    CUSTOM_MIRRORS, MIRRORS_PATH,
+    mgr::{CustomMirrorProblem, CustomMirrors, DistroConfig, Mirrors},
+    paths::Paths,
 };
 
-pub fn create_status<P: AsRef<Path>>(status: P) -> Result<File> {
-    let status = status.as_ref();
-    if let Some(parent) = status.parent() {
-        if !parent.is_dir() {
-            fs::create_dir_all(parent)?;
-        }
-    } else {
-        bail!("Unexpected path: {}", status.display())
-    }
-
-    let f = fs::OpenOptions::new()
-        .read(true)
-        .write(true)
-        .create(true)
-        .open(status)?;
-
-    Ok(f)
-}
-
+/// Refresh the APT metadata of the system under `paths`.
 #[cfg(any(feature = "oma-refresh", feature = "oma-refresh-aosc"))]
-pub fn refresh() -> Result<()> {
-    use std::{path::PathBuf, sync::{atomic::Ordering, Arc}};
+pub fn refresh(paths: &Paths) -> Result<()> {
+    use std::sync::{atomic::Ordering, Arc};
 
     use dashmap::DashMap;
     use indicatif::{MultiProgress, ProgressBar};
@@ -51,6 +29,8 @@ pub fn refresh() -> Result<()> {
     use std::sync::atomic::AtomicBool;
     use tokio::runtime::Builder;
 
+    use crate::fl;
+
     let mb = Arc::new(MultiProgress::new());
     let pb_map: DashMap<usize, ProgressBar> = DashMap::new();
     let global_is_set = Arc::new(AtomicBool::new(false));
@@ -59,13 +39,14 @@ pub fn refresh() -> Result<()> {
 
     let client = reqwest::Client::builder().user_agent("oma").build()?;
     let refresh: OmaRefresh = OmaRefreshBuilder {
-        source: PathBuf::from("/"),
+        source: paths.root().to_path_buf(),
         limit: Some(4),
-        arch: dpkg_arch("/")?,
-        download_dir: "/var/lib/apt/lists".into(),
+        arch: dpkg_arch(paths.root())?,
+        download_dir: paths.apt_lists(),
         download_compress: true,
         client: &client,
-    }.into();
+    }
+    .into();
 
     let pb = mb.add(ProgressBar::new_spinner());
 
@@ -165,9 +146,19 @@ pub fn refresh() -> Result<()> {
     Ok(())
 }
 
+/// Refresh the APT metadata of the system under `paths`.
 #[cfg(not(any(feature = "oma-refresh", feature = "oma-refresh-aosc")))]
-pub fn refresh() -> Result<()> {
-    let cmd = Command::new("apt").arg("update").output()?;
+pub fn refresh(paths: &Paths) -> Result<()> {
+    use eyre::bail;
+    use std::{path::Path, process::Command};
+
+    let mut cmd = Command::new("apt");
+
+    if paths.root() != Path::new("/") {
+        cmd.arg("-o").arg(format!("Dir={}", paths.root().display()));
+    }
+
+    let cmd = cmd.arg("update").output()?;
 
     let code = cmd.status.code();
 
@@ -178,35 +169,16 @@ pub fn refresh() -> Result<()> {
     Ok(())
 }
 
-pub fn root() -> Result<()> {
-    if process::geteuid().is_root() {
-        return Ok(());
-    }
-
-    let args = std::env::args().collect::<Vec<_>>();
+/// Load distro mirrors and merge custom mirrors, including drop-ins, into them. Custom mirror
+/// files which can not be parsed, duplicate names and names of distro mirrors are skipped, and
+/// returned for the caller to report.
+pub fn distro_and_custom_mirrors(paths: &Paths) -> Result<(Mirrors, Vec<CustomMirrorProblem>)> {
+    let mut all_mirrors = Mirrors::from_path(paths.mirrors())?;
+    let custom = CustomMirrors::load_all(paths, &all_mirrors);
 
-    let out = Command::new("pkexec")
-        .args(args)
-        .spawn()
-        .and_then(|x| x.wait_with_output())
-        .map_err(|e| anyhow!(fl!("execute-pkexec-fail", e = e.to_string())))?;
+    all_mirrors.init_custom_mirrors(custom.mirrors)?;
 
-    exit(
-        out.status
-            .code()
-            .expect("Can not get pkexec oma exit status"),
-    );
-}
-
-pub fn distro_and_custom_mirrors() -> Result<Mirrors> {
-    let mut all_mirrors = Mirrors::from_path(MIRRORS_PATH)?;
-    let custom = CustomMirrors::from_path(CUSTOM_MIRRORS);
-
-    if let Ok(custom) = custom {
-        all_mirrors.init_custom_mirrors(custom)?;
-    }
-
-    Ok(all_mirrors)
+    Ok((all_mirrors, custom.problems))
 }
 
 pub fn url_strip(url: &str) -> Cow<'_, str> {
@@ -216,3 +188,27 @@ pub fn url_strip(url: &str) -> Cow<'_, str> {
         Cow::Owned(format!("{url}/"))
     }
 }
+
+/// Seconds since the Unix epoch, the unit of stored timestamps.
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Time elapsed since `timestamp`, zero if it is in the future.
+pub fn age(timestamp: u64) -> Duration {
+    Duration::from_secs(now_secs().saturating_sub(timestamp))
+}
+
+pub fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+
+    match secs {
+        0..=59 => format!("{secs}s"),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
@@ -0,0 +1,157 @@
+use std::{fmt::Display, path::PathBuf};
+
+use eyre::Result;
+
+use crate::{
+    fl,
+    mgr::{read_or_empty, unified_diff, Branches, Comps, DistroConfig, MirrorManager, Mirrors},
+    paths::Paths,
+};
+
+/// Where the status, the APT sources on disk and the distro repository data disagree.
+pub enum Finding {
+    /// The sources file is not what the status renders to, i.e. it was edited by hand or the
+    /// status is out of date. `diff` turns the file on disk into the rendered one.
+    SourcesDrift {
+        path: PathBuf,
+        diff: String,
+    },
+    /// The sources file of the format not in use still exists
+    LegacySources {
+        path: PathBuf,
+    },
+    MirrorNotFound {
+        mirror: String,
+    },
+    MirrorRetired {
+        mirror: String,
+    },
+    /// The mirror was renamed, or replaced by `new`
+    MirrorReplaced {
+        mirror: String,
+        new: String,
+    },
+    /// The URL stored for an enabled mirror is not the one in the mirror list anymore
+    MirrorUrlChanged {
+        mirror: String,
+        old: String,
+        new: String,
+    },
+    BranchNotFound {
+        branch: String,
+    },
+    ComponentNotFound {
+        comp: String,
+    },
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Finding::SourcesDrift { path, .. } => {
+                fl!("drift-sources", path = path.display().to_string())
+            }
+            Finding::LegacySources { path } => {
+                fl!("drift-legacy-sources", path = path.display().to_string())
+            }
+            Finding::MirrorNotFound { mirror } => {
+                fl!("drift-mirror-not-found", mirror = mirror.as_str())
+            }
+            Finding::MirrorRetired { mirror } => {
+                fl!("drift-mirror-retired", mirror = mirror.as_str())
+            }
+            Finding::MirrorReplaced { mirror, new } => fl!(
+                "drift-mirror-replaced",
+                mirror = mirror.as_str(),
+                new = new.as_str()
+            ),
+            Finding::MirrorUrlChanged { mirror, old, new } => fl!(
+                "drift-mirror-url-changed",
+                mirror = mirror.as_str(),
+                old = old.as_str(),
+                new = new.as_str()
+            ),
+            Finding::BranchNotFound { branch } => {
+                fl!("drift-branch-not-found", branch = branch.as_str())
+            }
+            Finding::ComponentNotFound { comp } => {
+                fl!("drift-comp-not-found", comp = comp.as_str())
+            }
+        };
+
+        f.write_str(&s)
+    }
+}
+
+/// Check that every enabled mirror, the branch and every component still exist, and that the
+/// APT sources on disk are what mirrormgr would write.
+pub fn verify(
+    mm: &MirrorManager,
+    mirrors: &Mirrors,
+    branches: &Branches,
+    comps: &Comps,
+    paths: &Paths,
+) -> Result<Vec<Finding>> {
+    let mut res = vec![];
+    let status = mm.status();
+
+    // Sync a copy, to see what would change
+    let report = status.clone().sync_urls(mirrors);
+
+    for mirror in report.missing {
+        res.push(Finding::MirrorNotFound { mirror });
+    }
+
+    for mirror in status.mirrors().keys() {
+        if let Some(new) = mirrors.replacement(mirror) {
+            res.push(Finding::MirrorReplaced {
+                mirror: mirror.clone(),
+                new: new.to_string(),
+            });
+        }
+    }
+
+    for mirror in mm.retired_mirrors(mirrors) {
+        res.push(Finding::MirrorRetired {
+            mirror: mirror.to_string(),
+        });
+    }
+
+    for (mirror, old, new) in report.updated {
+        res.push(Finding::MirrorUrlChanged { mirror, old, new });
+    }
+
+    let has_branch = branches.has(status.branch());
+    if !has_branch {
+        res.push(Finding::BranchNotFound {
+            branch: status.branch().to_string(),
+        });
+    }
+
+    for comp in status.components() {
+        if !comps.has(comp) {
+            res.push(Finding::ComponentNotFound { comp: comp.clone() });
+        }
+    }
+
+    let (apt_path, legacy_path) = mm.sources_paths(paths);
+
+    // Sources can not be rendered without a branch
+    if has_branch {
+        let on_disk = read_or_empty(&apt_path)?;
+        let rendered = mm.try_to_string(branches, paths)?;
+
+        if on_disk != rendered {
+            res.push(Finding::SourcesDrift {
+                diff: unified_diff(&apt_path, &on_disk, &rendered),
+                path: apt_path,
+            });
+        }
+    }
+
+    if legacy_path.exists() {
+        res.push(Finding::LegacySources { path: legacy_path });
+    }
+
+    Ok(res)
+}